@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::prelude::*;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::{
+    game_state::PauseState,
+    graphics::{animation::Animation, y_sort::YSort},
+    mutant::{mutant_catch_player, Mutant, PlayerCaughtEvent},
+    player::Player,
+    spatial_index::{SpatialIndex, SpatialIndexed},
+    timestep::{fixed_timestep, FIXED_UPDATE, GAMEPLAY_SUBSTAGE},
+};
+
+/// Puts the player back at the last checkpoint they touched when caught.
+///
+/// Only the player and the mutants are moved back,
+/// the level itself stays spawned.
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep_system_set(
+            FIXED_UPDATE,
+            GAMEPLAY_SUBSTAGE,
+            ConditionSet::new()
+                .run_in_state(PauseState::Running)
+                .with_system(record_level_start)
+                .with_system(reach_checkpoint.after(record_level_start))
+                .with_system(start_dying.after(mutant_catch_player))
+                .with_system(respawn_at_checkpoint.after(start_dying))
+                .into(),
+        );
+    }
+}
+
+/// How close the player has to get to a checkpoint to reach it.
+const CHECKPOINT_RADIUS: f32 = 12.;
+/// Seconds between getting caught and respawning, long enough for the death animation.
+const RESPAWN_DELAY: f32 = 1.;
+
+#[derive(Clone, Eq, PartialEq, Debug, Default, Component)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
+pub struct Checkpoint {
+    /// Iid of the LDtk entity, unlike the `Entity` it survives the level respawning.
+    pub iid: String,
+}
+
+impl From<EntityInstance> for Checkpoint {
+    fn from(entity_instance: EntityInstance) -> Self {
+        Self {
+            iid: entity_instance.iid,
+        }
+    }
+}
+
+#[derive(Bundle, Default, LdtkEntity)]
+pub struct CheckpointBundle {
+    #[from_entity_instance]
+    checkpoint: Checkpoint,
+    #[with(checkpoint_sprite)]
+    #[bundle]
+    sprite_bundle: SpriteBundle,
+    y_sort: YSort,
+    spatial_indexed: SpatialIndexed,
+}
+
+fn checkpoint_sprite(_: EntityInstance) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: Color::rgb(0.3, 0.5, 0.9),
+            custom_size: Some(Vec2::new(4., 10.)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// The player was caught and respawns once the timer is done.
+#[derive(Clone, Debug, Default, Component)]
+pub struct Dying(pub Timer);
+
+/// Where everything was when the player reached the last checkpoint.
+///
+/// Translations are local, like the `Transform`s of LDtk entities.
+/// Everything is keyed by LDtk iids, so the state survives the world
+/// respawning in place.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CheckpointState {
+    /// Iid of the level the checkpoint is in.
+    pub level: String,
+    /// Iid of the `Checkpoint`, `None` for the start of the level.
+    pub checkpoint: Option<String>,
+    pub player: Vec3,
+    /// By iid, mutants spawned from the console aren't put back.
+    pub mutants: HashMap<String, Vec3>,
+}
+
+impl CheckpointState {
+    fn record(
+        level: String,
+        checkpoint: Option<String>,
+        player: &Transform,
+        mutant_query: &Query<(&EntityInstance, &Transform), (With<Mutant>, Without<Player>)>,
+    ) -> Self {
+        Self {
+            level,
+            checkpoint,
+            player: player.translation,
+            mutants: mutant_query
+                .iter()
+                .map(|(instance, transform)| (instance.iid.clone(), transform.translation))
+                .collect(),
+        }
+    }
+}
+
+/// Every level starts with an implicit checkpoint where the player spawns.
+///
+/// Respawning the world in place spawns a new player as well,
+/// but the last checkpoint of the level still counts.
+#[allow(clippy::type_complexity)]
+fn record_level_start(
+    mut commands: Commands,
+    state: Option<Res<CheckpointState>>,
+    player_query: Query<(Entity, &Transform), Added<Player>>,
+    mutant_query: Query<(&EntityInstance, &Transform), (With<Mutant>, Without<Player>)>,
+    parent_query: Query<&Parent>,
+    level_query: Query<&Handle<LdtkLevel>>,
+    levels: Res<Assets<LdtkLevel>>,
+) {
+    let (player, player_transform) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let level = match level_iid(player, &parent_query, &level_query, &levels) {
+        Some(level) => level,
+        None => return,
+    };
+    if state.map_or(false, |state| state.level == level) {
+        return;
+    }
+
+    commands.insert_resource(CheckpointState::record(
+        level,
+        None,
+        player_transform,
+        &mutant_query,
+    ));
+}
+
+/// Iid of the level `entity` was spawned in.
+///
+/// Read from the level entity itself, `CurrentLevel` may not have
+/// caught up yet on the tick the level's entities appear.
+fn level_iid(
+    entity: Entity,
+    parent_query: &Query<&Parent>,
+    level_query: &Query<&Handle<LdtkLevel>>,
+    levels: &Assets<LdtkLevel>,
+) -> Option<String> {
+    let mut ancestor = entity;
+    loop {
+        if let Ok(handle) = level_query.get(ancestor) {
+            return levels.get(handle).map(|level| level.level.iid.clone());
+        }
+        ancestor = parent_query.get(ancestor).ok()?.get();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn reach_checkpoint(
+    mut commands: Commands,
+    state: Option<Res<CheckpointState>>,
+    index: Res<SpatialIndex>,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Dying>)>,
+    checkpoint_query: Query<&Checkpoint>,
+    mutant_query: Query<(&EntityInstance, &Transform), (With<Mutant>, Without<Player>)>,
+) {
+    let state = match state {
+        Some(state) => state,
+        None => return,
+    };
+    let (player, player_transform) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let player_position = match index.position(player) {
+        Some(position) => position,
+        None => return,
+    };
+
+    let reached = index
+        .in_radius(player_position, CHECKPOINT_RADIUS)
+        .find_map(|entity| checkpoint_query.get(entity).ok());
+
+    if let Some(checkpoint) = reached {
+        if state.checkpoint.as_ref() != Some(&checkpoint.iid) {
+            info!("Reached checkpoint {}", checkpoint.iid);
+            commands.insert_resource(CheckpointState::record(
+                state.level.clone(),
+                Some(checkpoint.iid.clone()),
+                player_transform,
+                &mutant_query,
+            ));
+        }
+    }
+}
+
+fn start_dying(
+    mut commands: Commands,
+    mut caught_events: EventReader<PlayerCaughtEvent>,
+    player_query: Query<(), (With<Player>, Without<Dying>)>,
+) {
+    for event in caught_events.iter() {
+        if player_query.contains(event.player) {
+            commands
+                .entity(event.player)
+                .insert(Dying(Timer::from_seconds(RESPAWN_DELAY, false)));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn respawn_at_checkpoint(
+    mut commands: Commands,
+    state: Option<Res<CheckpointState>>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Dying,
+            &mut Transform,
+            &mut Velocity,
+            &mut Animation,
+        ),
+        With<Player>,
+    >,
+    mut mutant_query: Query<
+        (&EntityInstance, &mut Transform, &mut Velocity),
+        (With<Mutant>, Without<Player>),
+    >,
+) {
+    let (player, mut dying, mut player_transform, mut player_velocity, mut animation) =
+        match player_query.get_single_mut() {
+            Ok(player) => player,
+            Err(_) => return,
+        };
+
+    dying.0.tick(fixed_timestep());
+    if !dying.0.finished() {
+        return;
+    }
+
+    if let Some(state) = state {
+        player_transform.translation = state.player;
+        *player_velocity = Velocity::zero();
+
+        for (instance, mut transform, mut velocity) in mutant_query.iter_mut() {
+            if let Some(translation) = state.mutants.get(&instance.iid) {
+                transform.translation = *translation;
+                *velocity = Velocity::zero();
+            }
+        }
+    }
+
+    animation.reset();
+    commands.entity(player).remove::<Dying>();
+}
@@ -29,4 +29,5 @@ impl From<EntityInstance> for ColliderBundle {
 pub const WALL_PHYS_LAYER: Group = Group::GROUP_1;
 pub const PLAYER_PHYS_LAYER: Group = Group::GROUP_2;
 pub const MUTANT_PHYS_LAYER: Group = Group::GROUP_3;
+pub const ITEM_PHYS_LAYER: Group = Group::GROUP_4;
 pub const SOUND_PHYS_LAYER: Group = Group::GROUP_10;
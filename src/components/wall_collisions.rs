@@ -1,10 +1,21 @@
 use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::{FieldValue, Level};
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use std::collections::{HashMap, HashSet};
 
 use super::physics::WALL_PHYS_LAYER;
+use crate::utils::{
+    ldtk_fields::level_field,
+    wall_merge::{
+        check_cover, greedy_merge_walls, merge_walls, trace_wall_outlines, MergeStats, Rect,
+        WallDecomposition,
+    },
+};
+
+/// Identifier of the optional level field that picks the `WallDecomposition`.
+const WALL_COLLIDERS_FIELD: &str = "WallColliders";
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Wall;
@@ -17,21 +28,32 @@ pub struct WallBundle {
     pub wall: Wall,
 }
 
-/// Represents a wide wall that is 1 tile tall
-/// Used to spawn wall collisions
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
-struct Plate {
-    left: i32,
-    right: i32,
-}
+/// Collider counts from the last time each level's walls were merged,
+/// keyed by level iid.
+#[derive(Clone, Debug, Default)]
+pub struct WallMergeStats(pub HashMap<String, MergeStats>);
 
-/// A simple rectangle type representing a wall of any size
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
-struct Rect {
-    left: i32,
-    right: i32,
-    top: i32,
-    bottom: i32,
+/// Keeps `WallMergeStats` in step with the levels that are spawned.
+///
+/// A level's stats are dropped when it starts spawning again or goes away,
+/// so stats from an earlier spawn never stand in for the new one.
+/// Levels without walls get empty stats once they have spawned,
+/// `spawn_wall_collision` has seen the walls of a level by then.
+pub fn track_wall_merge_stats(
+    mut level_events: EventReader<LevelEvent>,
+    mut merge_stats: ResMut<WallMergeStats>,
+) {
+    for level_event in level_events.iter() {
+        match level_event {
+            LevelEvent::SpawnTriggered(iid) | LevelEvent::Despawned(iid) => {
+                merge_stats.0.remove(iid);
+            }
+            LevelEvent::Spawned(iid) => {
+                merge_stats.0.entry(iid.clone()).or_default();
+            }
+            LevelEvent::Transformed(_) => (),
+        }
+    }
 }
 
 /// Spawns rapier collisions for the walls of a level
@@ -43,19 +65,17 @@ struct Rect {
 /// Instead, by flagging the wall tiles and spawning the collisions later,
 /// we can minimize the amount of colliding entities.
 ///
-/// The algorithm used here is a nice compromise between simplicity, speed,
-/// and a small number of rectangle colliders.
 /// In basic terms, it will:
 /// 1. consider where the walls are
-/// 2. combine wall tiles into flat "plates" in each individual row
-/// 3. combine the plates into rectangles across multiple rows wherever possible
-/// 4. spawn colliders for each rectangle
+/// 2. merge the wall tiles into rectangles or outlines, see `utils::wall_merge`
+/// 3. spawn colliders for each rectangle or outline
 pub fn spawn_wall_collision(
     mut commands: Commands,
     wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
     parent_query: Query<&Parent, Without<Wall>>,
     level_query: Query<(Entity, &Handle<LdtkLevel>)>,
     levels: Res<Assets<LdtkLevel>>,
+    mut merge_stats: ResMut<WallMergeStats>,
 ) {
     // Consider where the walls are
     // storing them as GridCoords in a HashSet for quick, easy lookup
@@ -84,82 +104,120 @@ pub fn spawn_wall_collision(
                 let height = layer_instance.c_hei;
                 let grid_size = layer_instance.grid_size;
 
-                // combine wall tiles into flat "plates" in each individual row
-                let mut plate_stack = combine_walls_into_plates(width, height, level_walls);
-
-                // combine "plates" into rectangles across multiple rows
-                let wall_rects = fill_wall_rects(&mut plate_stack);
-
-                commands.entity(level_entity).with_children(|level| {
-                    // Spawn colliders for every rectangle..
-                    // Making the collider a child of the level serves two purposes:
-                    // 1. Adjusts the transforms to be relative to the level for free
-                    // 2. the colliders will be despawned automatically when levels unload
-                    for wall_rect in wall_rects {
-                        level
-                            .spawn()
-                            .insert(WallCollider)
-                            .insert(Collider::cuboid(
-                                ((wall_rect.right - wall_rect.left + 1) * grid_size) as f32 / 2.,
-                                ((wall_rect.top - wall_rect.bottom + 1) * grid_size) as f32 / 2.,
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(Friction {
-                                coefficient: 0.1,
-                                combine_rule: CoefficientCombineRule::Min,
-                            })
-                            .insert(Transform::from_xyz(
-                                ((wall_rect.left + wall_rect.right + 1) * grid_size) as f32 / 2.,
-                                ((wall_rect.bottom + wall_rect.top + 1) * grid_size) as f32 / 2.,
-                                0.,
-                            ))
-                            .insert(CollisionGroups::new(WALL_PHYS_LAYER, Group::all()))
-                            .insert(GlobalTransform::default());
-                    }
-                });
+                commands
+                    .entity(level_entity)
+                    .with_children(|level_commands| {
+                        // Making the colliders children of the level serves two purposes:
+                        // 1. Adjusts the transforms to be relative to the level for free
+                        // 2. the colliders will be despawned automatically when levels unload
+                        let stats = spawn_level_walls(
+                            level_commands,
+                            &level.level,
+                            level_walls,
+                            width,
+                            height,
+                            grid_size,
+                        );
+
+                        info!("{}: {}", level.level.identifier, stats);
+                        merge_stats.0.insert(level.level.iid.clone(), stats);
+                    });
             }
         });
     }
 }
 
-fn fill_wall_rects(plate_stack: &mut Vec<Vec<Plate>>) -> Vec<Rect> {
-    let mut wall_rects: Vec<Rect> = Vec::new();
-    let mut previous_rects: HashMap<Plate, Rect> = HashMap::new();
-
-    // an extra empty row so the algorithm "terminates" the rects that touch the top
-    // edge
-    plate_stack.push(Vec::new());
-
-    for (y, row) in plate_stack.iter().enumerate() {
-        let mut current_rects: HashMap<Plate, Rect> = HashMap::new();
-        for plate in row {
-            if let Some(previous_rect) = previous_rects.remove(plate) {
-                current_rects.insert(
-                    *plate,
-                    Rect {
-                        top: previous_rect.top + 1,
-                        ..previous_rect
-                    },
-                );
-            } else {
-                current_rects.insert(
-                    *plate,
-                    Rect {
-                        bottom: y as i32,
-                        top: y as i32,
-                        left: plate.left,
-                        right: plate.right,
-                    },
-                );
+/// Merges the walls of one level with the level's `WallDecomposition`
+/// and spawns their colliders.
+fn spawn_level_walls(
+    level_commands: &mut ChildBuilder,
+    level: &Level,
+    level_walls: &HashSet<GridCoords>,
+    width: i32,
+    height: i32,
+    grid_size: i32,
+) -> MergeStats {
+    let decomposition = level_wall_decomposition(level);
+
+    let wall_rects = match decomposition {
+        WallDecomposition::Rows => merge_walls(width, height, level_walls),
+        WallDecomposition::Greedy => greedy_merge_walls(width, height, level_walls),
+        WallDecomposition::Outline => {
+            let outlines = trace_wall_outlines(level_walls);
+
+            for outline in &outlines {
+                spawn_wall_outline(level_commands, outline, grid_size);
             }
+
+            return MergeStats::for_outlines(level_walls, &outlines);
         }
+    };
 
-        // Any plates that weren't removed above have terminated
-        wall_rects.append(&mut previous_rects.values().copied().collect());
-        previous_rects = current_rects;
+    if cfg!(debug_assertions) {
+        if let Err(error) = check_cover(level_walls, &wall_rects) {
+            error!("Bad wall merge in {}: {}", level.identifier, error);
+        }
     }
 
-    wall_rects
+    for wall_rect in &wall_rects {
+        spawn_wall_rect(level_commands, wall_rect, grid_size);
+    }
+
+    MergeStats::for_rects(decomposition, level_walls, &wall_rects)
+}
+
+fn level_wall_decomposition(level: &Level) -> WallDecomposition {
+    let name = match level_field(level, WALL_COLLIDERS_FIELD) {
+        Some(FieldValue::String(Some(name))) | Some(FieldValue::Enum(Some(name))) => name,
+        _ => return WallDecomposition::default(),
+    };
+
+    name.parse().unwrap_or_else(|error| {
+        warn!("{}: {}, using the default", level.identifier, error);
+        WallDecomposition::default()
+    })
+}
+
+/// Common components of every wall collider entity.
+fn wall_collider_bundle() -> impl Bundle {
+    (
+        WallCollider,
+        RigidBody::Fixed,
+        Friction {
+            coefficient: 0.1,
+            combine_rule: CoefficientCombineRule::Min,
+        },
+        CollisionGroups::new(WALL_PHYS_LAYER, Group::all()),
+        GlobalTransform::default(),
+    )
+}
+
+pub fn spawn_wall_rect(level: &mut ChildBuilder, wall_rect: &Rect, grid_size: i32) {
+    level
+        .spawn_bundle(wall_collider_bundle())
+        .insert(Collider::cuboid(
+            (wall_rect.width() * grid_size) as f32 / 2.,
+            (wall_rect.height() * grid_size) as f32 / 2.,
+        ))
+        .insert(Transform::from_xyz(
+            ((wall_rect.left + wall_rect.right + 1) * grid_size) as f32 / 2.,
+            ((wall_rect.bottom + wall_rect.top + 1) * grid_size) as f32 / 2.,
+            0.,
+        ));
+}
+
+pub fn spawn_wall_outline(level: &mut ChildBuilder, outline: &[IVec2], grid_size: i32) {
+    // Close the loop by ending where the outline started
+    let vertices: Vec<Vec2> = outline
+        .iter()
+        .chain(outline.first())
+        .map(|corner| (*corner * grid_size).as_vec2())
+        .collect();
+
+    level
+        .spawn_bundle(wall_collider_bundle())
+        .insert(Collider::polyline(vertices, None))
+        .insert(Transform::default());
 }
 
 fn parse_level_to_wall(
@@ -182,36 +240,3 @@ fn parse_level_to_wall(
 
     level_to_wall_locations
 }
-
-fn combine_walls_into_plates(
-    width: i32,
-    height: i32,
-    level_walls: &HashSet<GridCoords>,
-) -> Vec<Vec<Plate>> {
-    let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-    for y in 0..height {
-        let mut row_plates: Vec<Plate> = Vec::new();
-        let mut plate_start = None;
-
-        // + 1 to the width so the algorithm "terminates" plates that touch the right
-        // edge
-        for x in 0..width + 1 {
-            match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                (Some(s), false) => {
-                    row_plates.push(Plate {
-                        left: s,
-                        right: x - 1,
-                    });
-                    plate_start = None;
-                }
-                (None, true) => plate_start = Some(x),
-                _ => (),
-            }
-        }
-
-        plate_stack.push(row_plates);
-    }
-
-    plate_stack
-}
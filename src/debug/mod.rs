@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{prelude::*, widgets::*};
+use bevy_pixel_camera::PixelProjection;
+
+use crate::{
+    graphics::{
+        camera::{CameraAnchor, CurrentCameraAnchorEntityId},
+        camera_shake::CameraShake,
+        camera_zoom::CameraZoom,
+    },
+    mutant::Mutant,
+    player::{MovementForce, NoiseBudget, Player},
+};
+
+#[derive(Inspectable, Default)]
+pub struct Entities {
+    #[inspectable(collapse, label = "Player")]
+    player: InspectorQuerySingle<Entity, With<Player>>,
+    #[inspectable(collapse, label = "Mutants")]
+    mutants: InspectorQuery<Entity, With<Mutant>>,
+}
+
+/// The world and entity inspector windows.
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(WorldInspectorPlugin::new())
+            .add_plugin(InspectorPlugin::<Entities>::new())
+            .register_type::<PixelProjection>()
+            .register_inspectable::<Player>()
+            .register_inspectable::<CameraAnchor>()
+            .register_inspectable::<MovementForce>()
+            .register_inspectable::<NoiseBudget>()
+            .register_inspectable::<CurrentCameraAnchorEntityId>()
+            .register_inspectable::<CameraShake>()
+            .register_inspectable::<CameraZoom>();
+    }
+}
@@ -0,0 +1,391 @@
+use anyhow::{anyhow, bail};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use iyes_loopless::prelude::*;
+
+use std::str::FromStr;
+
+use crate::{
+    dev_tools::has_egui_context,
+    game_state::GameState,
+    hot_reload::respawn_world_in_place,
+    input::{collect_presses, update_actions, Actions},
+    mutant::MutantBundle,
+    player::{MovementForce, Player, SoundWave, SoundWaveEvent},
+    resources::{Cheats, CurrentLevel, WorldAssets},
+    timestep::set_time_scale,
+};
+
+/// How many lines of output the console keeps.
+const MAX_OUTPUT_LINES: usize = 100;
+
+/// Where `spawn mutant` puts the mutant, relative to the player.
+const SPAWN_OFFSET: Vec3 = Vec3::new(48., 0., 0.);
+
+const HELP: &str = "level <iid or identifier>, spawn mutant, god, noclip, reveal_all, \
+                    set movement_force <force>, timescale <scale>, stress <waves>, help";
+
+/// A console for changing the game while playtesting, toggled with the backtick key.
+///
+/// The player doesn't react to input while it's open.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_event::<ConsoleCommand>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                ignore_actions_while_open
+                    .after(update_actions)
+                    .before(collect_presses),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Playing)
+                    .with_system(toggle_console)
+                    .with_system(run_console_commands.after(console_window))
+                    .with_system(reveal_all.after(run_console_commands))
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Playing)
+                    .run_if(has_egui_context)
+                    .after(toggle_console)
+                    .with_system(console_window)
+                    .into(),
+            );
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: Vec<String>,
+}
+
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+        if self.output.len() > MAX_OUTPUT_LINES {
+            self.output.remove(0);
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConsoleCommand {
+    /// Switches to the level with this iid or identifier.
+    Level(String),
+    SpawnMutant,
+    /// Toggles `Cheats::god`.
+    God,
+    /// Toggles `Cheats::noclip`.
+    Noclip,
+    /// Toggles `Cheats::reveal_all`.
+    RevealAll,
+    MovementForce(f32),
+    TimeScale(f32),
+    /// Claps this many times at once where the player stands,
+    /// to see how the game copes with lots of sound dots.
+    Stress(u32),
+    Help,
+}
+
+impl FromStr for ConsoleCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+
+        let command = match words.as_slice() {
+            ["level", level] => ConsoleCommand::Level(level.to_string()),
+            ["spawn", "mutant"] => ConsoleCommand::SpawnMutant,
+            ["god"] => ConsoleCommand::God,
+            ["noclip"] => ConsoleCommand::Noclip,
+            ["reveal_all"] => ConsoleCommand::RevealAll,
+            ["set", "movement_force", force] => ConsoleCommand::MovementForce(parse_number(force)?),
+            ["set", setting, _] => bail!("Unknown setting: {}", setting),
+            ["timescale", scale] => {
+                let scale = parse_number(scale)?;
+                if scale <= 0. {
+                    bail!("The timescale has to be above 0");
+                }
+                ConsoleCommand::TimeScale(scale)
+            }
+            ["stress", waves] => ConsoleCommand::Stress(
+                waves
+                    .parse()
+                    .map_err(|_| anyhow!("Not a number of waves: {}", waves))?,
+            ),
+            ["help"] => ConsoleCommand::Help,
+            _ => bail!("Unknown command: {}, try help", s),
+        };
+
+        Ok(command)
+    }
+}
+
+fn parse_number(s: &str) -> anyhow::Result<f32> {
+    s.parse().map_err(|_| anyhow!("Not a number: {}", s))
+}
+
+fn toggle_console(keyboard: Res<Input<KeyCode>>, mut console: ResMut<Console>) {
+    if keyboard.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+    }
+}
+
+/// Typing a command shouldn't move the player or make sounds.
+fn ignore_actions_while_open(console: Res<Console>, mut actions: ResMut<Actions>) {
+    if console.open {
+        *actions = Actions::default();
+    }
+}
+
+fn console_window(
+    mut egui_context: ResMut<EguiContext>,
+    mut console: ResMut<Console>,
+    mut command_events: EventWriter<ConsoleCommand>,
+) {
+    if !console.open {
+        return;
+    }
+    // The key that opened the console would end up in the input
+    console.input.retain(|c| c != '`');
+
+    let mut submitted = None;
+    egui::Window::new("Console")
+        .anchor(egui::Align2::CENTER_TOP, [0., 8.])
+        .collapsible(false)
+        .resizable(false)
+        .default_width(480.)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.)
+                .show(ui, |ui| {
+                    for line in &console.output {
+                        ui.monospace(line);
+                    }
+                });
+
+            let input = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .desired_width(f32::INFINITY)
+                    .font(egui::TextStyle::Monospace),
+            );
+            if input.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                submitted = Some(std::mem::take(&mut console.input));
+            }
+            input.request_focus();
+        });
+
+    let line = match submitted {
+        Some(line) if !line.trim().is_empty() => line,
+        _ => return,
+    };
+
+    console.print(format!("> {}", line.trim()));
+    match line.parse() {
+        Ok(command) => command_events.send(command),
+        Err(error) => console.print(error.to_string()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_console_commands(
+    mut commands: Commands,
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut cheats: ResMut<Cheats>,
+    mut timesteps: ResMut<FixedTimesteps>,
+    mut wave_events: EventWriter<SoundWaveEvent>,
+    mut player_query: Query<
+        (&Transform, &GlobalTransform, &Parent, &mut MovementForce),
+        With<Player>,
+    >,
+    world_assets: Res<WorldAssets>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+) {
+    for command in command_events.iter() {
+        let reply = match command {
+            ConsoleCommand::Level(name) => ldtk_assets
+                .get(&world_assets.map)
+                .and_then(|ldtk_asset| {
+                    ldtk_asset
+                        .project
+                        .levels
+                        .iter()
+                        .find(|level| level.iid == *name || level.identifier == *name)
+                })
+                .map(|level| {
+                    commands.insert_resource(LevelSelection::Iid(level.iid.clone()));
+                    format!("Going to {}", level.identifier)
+                })
+                .ok_or_else(|| anyhow!("No level called {}", name)),
+            ConsoleCommand::SpawnMutant => player_query
+                .get_single()
+                .map(|(transform, _, parent, _)| {
+                    MutantBundle::spawn(
+                        &mut commands,
+                        parent.get(),
+                        transform.translation + SPAWN_OFFSET,
+                    );
+                    "Spawned a mutant next to the player".to_string()
+                })
+                .map_err(|_| anyhow!("There is no player to spawn it next to")),
+            ConsoleCommand::God => {
+                cheats.god = !cheats.god;
+                Ok(format!("God mode {}", on_off(cheats.god)))
+            }
+            ConsoleCommand::Noclip => {
+                cheats.noclip = !cheats.noclip;
+                Ok(format!("Noclip {}", on_off(cheats.noclip)))
+            }
+            ConsoleCommand::RevealAll => {
+                cheats.reveal_all = !cheats.reveal_all;
+                Ok(format!("Reveal all {}", on_off(cheats.reveal_all)))
+            }
+            ConsoleCommand::MovementForce(force) => player_query
+                .get_single_mut()
+                .map(|(_, _, _, mut movement_force)| {
+                    movement_force.0 = *force;
+                    format!("Movement force set to {}", force)
+                })
+                .map_err(|_| anyhow!("There is no player")),
+            ConsoleCommand::TimeScale(scale) => {
+                set_time_scale(&mut timesteps, *scale);
+                Ok(format!("Timescale set to {}", scale))
+            }
+            ConsoleCommand::Stress(waves) => player_query
+                .get_single()
+                .map(|(_, global_transform, _, _)| {
+                    let origin = global_transform.translation();
+                    for _ in 0..*waves {
+                        wave_events.send(SoundWaveEvent {
+                            origin,
+                            wave: SoundWave::CLAP,
+                        });
+                    }
+                    format!(
+                        "Sent {} waves of {} dots",
+                        waves,
+                        SoundWave::CLAP.number_of_dots
+                    )
+                })
+                .map_err(|_| anyhow!("There is no player to make the noise")),
+            ConsoleCommand::Help => Ok(HELP.to_string()),
+        };
+
+        match reply {
+            Ok(reply) => console.print(reply),
+            Err(error) => console.print(error.to_string()),
+        }
+    }
+}
+
+/// The collider layer is only drawn or hidden when the level spawns,
+/// so switching `Cheats::reveal_all` respawns the world around the player.
+fn reveal_all(
+    mut commands: Commands,
+    cheats: Res<Cheats>,
+    mut ldtk_settings: ResMut<LdtkSettings>,
+    world_query: Query<(Entity, &Handle<LdtkAsset>)>,
+    player_query: Query<&Transform, With<Player>>,
+    current_level: Res<CurrentLevel>,
+    world_assets: Res<WorldAssets>,
+) {
+    let revealed = matches!(ldtk_settings.int_grid_rendering, IntGridRendering::Colorful);
+    if revealed == cheats.reveal_all {
+        return;
+    }
+
+    ldtk_settings.int_grid_rendering = if cheats.reveal_all {
+        IntGridRendering::Colorful
+    } else {
+        IntGridRendering::Invisible
+    };
+    respawn_world_in_place(
+        &mut commands,
+        &world_query,
+        &player_query,
+        &current_level,
+        &world_assets.map,
+    );
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> String {
+        line.parse::<ConsoleCommand>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            "level Level_0".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::Level("Level_0".to_string())
+        );
+        assert_eq!(
+            "  spawn   mutant ".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::SpawnMutant
+        );
+        assert_eq!(
+            "set movement_force 2.5".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::MovementForce(2.5)
+        );
+        assert_eq!(
+            "timescale 0.5".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::TimeScale(0.5)
+        );
+        assert_eq!(
+            "stress 20".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::Stress(20)
+        );
+        assert_eq!(
+            "reveal_all".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::RevealAll
+        );
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert_eq!(error("set gravity 3"), "Unknown setting: gravity");
+    }
+
+    #[test]
+    fn timescale_has_to_be_positive() {
+        assert_eq!(error("timescale 0"), "The timescale has to be above 0");
+        assert_eq!(error("timescale -1"), "The timescale has to be above 0");
+        assert_eq!(error("timescale fast"), "Not a number: fast");
+    }
+
+    #[test]
+    fn stress_needs_a_whole_number_of_waves() {
+        assert_eq!(error("stress abc"), "Not a number of waves: abc");
+        assert_eq!(error("stress -3"), "Not a number of waves: -3");
+    }
+
+    #[test]
+    fn unknown_commands_point_to_help() {
+        assert_eq!(error("fly"), "Unknown command: fly, try help");
+        assert_eq!(error("god mode"), "Unknown command: god mode, try help");
+        assert_eq!(
+            error("reveal_all walls"),
+            "Unknown command: reveal_all walls, try help"
+        );
+    }
+}
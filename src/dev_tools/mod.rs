@@ -0,0 +1,32 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContext;
+
+use console::ConsolePlugin;
+use overlay::OverlayPlugin;
+use telemetry::TelemetryPlugin;
+
+pub mod console;
+pub mod overlay;
+pub mod telemetry;
+
+/// The overlay, console and telemetry panel for playtesting.
+///
+/// Works in a `headless_app` too, the windows just aren't drawn there,
+/// so tests can run console commands and read the telemetry.
+pub struct DevToolsPlugin;
+
+impl Plugin for DevToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(OverlayPlugin)
+            .add_plugin(ConsolePlugin)
+            .add_plugin(TelemetryPlugin);
+    }
+}
+
+/// Run condition for the systems drawing the dev tools.
+///
+/// A `headless_app` has no `EguiPlugin`, so there is nothing to draw on,
+/// but everything else in the dev tools keeps working there.
+pub fn has_egui_context(egui_context: Option<Res<EguiContext>>) -> bool {
+    egui_context.is_some()
+}
@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_pixel_camera::PixelProjection;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use std::collections::VecDeque;
+
+use crate::{
+    components::wall_collisions::WallCollider, dev_tools::has_egui_context, game_state::GameState,
+    mutant::Hearing, player::SoundPoint,
+};
+
+/// How many positions of a sound dot's path are kept.
+const MAX_TRAIL_POINTS: usize = 120;
+
+const WALL_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 120, 40);
+const TRAIL_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 200, 255);
+const BOUNCE_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 60, 60);
+const HEARING_COLOR: egui::Color32 = egui::Color32::from_rgb(200, 90, 255);
+
+/// Draws debug information over the game.
+///
+/// Every layer has its own hotkey:
+/// - F1: the merged wall colliders, labelled with their entity id
+/// - F2: the paths of the sound dots and where they bounced
+/// - F4: how far every mutant hears and the closest sound it hears
+///
+/// Mutants don't have any AI yet, so there is no AI state or planned path to draw.
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Playing)
+                    .with_system(toggle_overlay_layers)
+                    .with_system(record_sound_trails)
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Playing)
+                    .run_if(has_egui_context)
+                    .after(toggle_overlay_layers)
+                    .after(record_sound_trails)
+                    .with_system(draw_overlay)
+                    .into(),
+            );
+    }
+}
+
+/// Which layers of the overlay are shown.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct DebugOverlay {
+    pub walls: bool,
+    pub sound_paths: bool,
+    pub hearing: bool,
+}
+
+/// Where a sound dot has been, only recorded while `DebugOverlay::sound_paths` is on.
+#[derive(Clone, PartialEq, Debug, Default, Component)]
+struct SoundTrail {
+    points: VecDeque<Vec2>,
+    bounces: Vec<Vec2>,
+    velocity: Vec2,
+}
+
+fn toggle_overlay_layers(keyboard: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keyboard.just_pressed(KeyCode::F1) {
+        overlay.walls = !overlay.walls;
+    }
+    if keyboard.just_pressed(KeyCode::F2) {
+        overlay.sound_paths = !overlay.sound_paths;
+    }
+    if keyboard.just_pressed(KeyCode::F4) {
+        overlay.hearing = !overlay.hearing;
+    }
+}
+
+/// Only the walls change the velocity of a sound dot, so a new velocity is a bounce.
+fn record_sound_trails(
+    mut commands: Commands,
+    mut sound_query: Query<
+        (Entity, &Transform, &Velocity, Option<&mut SoundTrail>),
+        With<SoundPoint>,
+    >,
+    overlay: Res<DebugOverlay>,
+) {
+    if !overlay.sound_paths {
+        return;
+    }
+
+    for (entity, transform, velocity, trail) in sound_query.iter_mut() {
+        let position = transform.translation.truncate();
+
+        let mut trail = match trail {
+            Some(trail) => trail,
+            None => {
+                commands.entity(entity).insert(SoundTrail {
+                    points: VecDeque::from([position]),
+                    bounces: Vec::new(),
+                    velocity: velocity.linvel,
+                });
+                continue;
+            }
+        };
+
+        if trail.points.back() != Some(&position) {
+            trail.points.push_back(position);
+            if trail.points.len() > MAX_TRAIL_POINTS {
+                trail.points.pop_front();
+            }
+        }
+
+        if trail.velocity != velocity.linvel {
+            trail.bounces.push(position);
+            trail.velocity = velocity.linvel;
+        }
+    }
+}
+
+fn draw_overlay(
+    mut egui_context: ResMut<EguiContext>,
+    overlay: Res<DebugOverlay>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PixelProjection>>,
+    wall_query: Query<(Entity, &Collider, &GlobalTransform), With<WallCollider>>,
+    trail_query: Query<&SoundTrail>,
+    hearing_query: Query<(&Hearing, &GlobalTransform)>,
+) {
+    if *overlay == DebugOverlay::default() {
+        return;
+    }
+    let (camera, camera_transform) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let viewport_height = match camera.logical_viewport_size() {
+        Some(size) => size.y,
+        None => return,
+    };
+
+    // Bevy's viewport starts at the bottom, egui's at the top
+    let to_screen = |world: Vec2| {
+        camera
+            .world_to_viewport(camera_transform, world.extend(0.))
+            .map(|viewport| egui::pos2(viewport.x, viewport_height - viewport.y))
+    };
+
+    let painter = egui_context.ctx_mut().layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("debug_overlay"),
+    ));
+    let font = egui::FontId::monospace(10.);
+
+    if overlay.walls {
+        let stroke = egui::Stroke::new(1., WALL_COLOR);
+
+        for (entity, collider, transform) in wall_query.iter() {
+            let center = transform.translation().truncate();
+            let label_position;
+
+            if let Some(cuboid) = collider.as_cuboid() {
+                let half_extents = cuboid.half_extents();
+                let corners = (
+                    to_screen(center - half_extents),
+                    to_screen(center + half_extents),
+                );
+                if let (Some(a), Some(b)) = corners {
+                    painter.rect_stroke(egui::Rect::from_two_pos(a, b), 0., stroke);
+                }
+                label_position = to_screen(center);
+            } else if let Some(polyline) = collider.as_polyline() {
+                let vertices: Vec<_> = polyline
+                    .vertices()
+                    .filter_map(|vertex| to_screen(center + vertex))
+                    .collect();
+                for segment in vertices.windows(2) {
+                    painter.line_segment([segment[0], segment[1]], stroke);
+                }
+                label_position = vertices.first().copied();
+            } else {
+                continue;
+            }
+
+            if let Some(position) = label_position {
+                painter.text(
+                    position,
+                    egui::Align2::CENTER_CENTER,
+                    entity.id(),
+                    font.clone(),
+                    WALL_COLOR,
+                );
+            }
+        }
+    }
+
+    if overlay.sound_paths {
+        let stroke = egui::Stroke::new(1., TRAIL_COLOR.linear_multiply(0.5));
+
+        for trail in trail_query.iter() {
+            let points: Vec<_> = trail
+                .points
+                .iter()
+                .filter_map(|&point| to_screen(point))
+                .collect();
+            for segment in points.windows(2) {
+                painter.line_segment([segment[0], segment[1]], stroke);
+            }
+
+            for bounce in trail.bounces.iter().filter_map(|&bounce| to_screen(bounce)) {
+                painter.circle_filled(bounce, 2., BOUNCE_COLOR);
+            }
+        }
+    }
+
+    if overlay.hearing {
+        let stroke = egui::Stroke::new(1., HEARING_COLOR);
+
+        for (hearing, transform) in hearing_query.iter() {
+            let center = transform.translation().truncate();
+            let (screen_center, screen_edge) = match (
+                to_screen(center),
+                to_screen(center + Vec2::X * hearing.radius),
+            ) {
+                (Some(center), Some(edge)) => (center, edge),
+                _ => continue,
+            };
+            painter.circle_stroke(screen_center, screen_edge.x - screen_center.x, stroke);
+
+            if let Some(heard) = hearing.heard.and_then(to_screen) {
+                painter.line_segment([screen_center, heard], stroke);
+                painter.circle_filled(heard, 2., HEARING_COLOR);
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use std::time::Instant;
+
+use crate::{
+    dev_tools::has_egui_context, game_state::GameState, mutant::HearingChecks, player::SoundPoint,
+    spatial_index::SpatialIndex,
+};
+
+pub const FIXED_UPDATE_TIME: DiagnosticId =
+    DiagnosticId::from_u128(0x6b0d_2f51_93c4_4a7e_8d1e_3a5c_7f20_b914);
+pub const UPDATE_TIME: DiagnosticId =
+    DiagnosticId::from_u128(0x2e7a_c4d8_15f3_4b69_a0c2_9d64_e8b1_5f37);
+pub const POST_UPDATE_TIME: DiagnosticId =
+    DiagnosticId::from_u128(0xd93f_61b2_7a08_4ce5_b5d7_04e9_2c6a_81f3);
+
+/// Frames the stage timings are averaged over.
+const TIMING_HISTORY: usize = 60;
+
+/// A panel with the frame rate, entity counts and how long the stages take,
+/// toggled with F3.
+///
+/// Bevy can't time single systems without its `trace` feature, so stages
+/// are timed instead. The fixed update covers every tick of the frame,
+/// with `wall_sound_collisions`, `kill_old_sounds` and the physics step.
+/// The console's `stress` command fills the level with sound dots to
+/// watch them go up.
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        // `DefaultPlugins` adds it, the `MinimalPlugins` of a `headless_app` don't
+        app.init_resource::<Diagnostics>()
+            .add_plugin(FrameTimeDiagnosticsPlugin)
+            .init_resource::<Telemetry>()
+            .init_resource::<StageClock>()
+            .add_startup_system(register_stage_timings)
+            // The fixed update stage runs between the end of `PreUpdate`
+            // and the start of `Update`
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                start_clock.exclusive_system().at_end(),
+            )
+            .add_system_to_stage(
+                CoreStage::Update,
+                lap_clock(FIXED_UPDATE_TIME).exclusive_system().at_start(),
+            )
+            .add_system_to_stage(
+                CoreStage::Update,
+                lap_clock(UPDATE_TIME).exclusive_system().at_end(),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                lap_clock(POST_UPDATE_TIME).exclusive_system().at_end(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Playing)
+                    .with_system(toggle_telemetry)
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Playing)
+                    .run_if(has_egui_context)
+                    .after(toggle_telemetry)
+                    .with_system(telemetry_window)
+                    .into(),
+            );
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Telemetry {
+    pub open: bool,
+}
+
+/// When the stage being timed started.
+#[derive(Copy, Clone, Debug, Default)]
+struct StageClock(Option<Instant>);
+
+fn register_stage_timings(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(
+        FIXED_UPDATE_TIME,
+        "fixed_update_ms",
+        TIMING_HISTORY,
+    ));
+    diagnostics.add(Diagnostic::new(UPDATE_TIME, "update_ms", TIMING_HISTORY));
+    diagnostics.add(Diagnostic::new(
+        POST_UPDATE_TIME,
+        "post_update_ms",
+        TIMING_HISTORY,
+    ));
+}
+
+fn start_clock(world: &mut World) {
+    world.resource_mut::<StageClock>().0 = Some(Instant::now());
+}
+
+/// Measures the time since the clock was started and starts it again
+/// for the next stage.
+fn lap_clock(id: DiagnosticId) -> impl FnMut(&mut World) {
+    move |world: &mut World| {
+        let now = Instant::now();
+        if let Some(start) = world.resource_mut::<StageClock>().0.replace(now) {
+            let milliseconds = (now - start).as_secs_f64() * 1000.;
+            world
+                .resource_mut::<Diagnostics>()
+                .add_measurement(id, || milliseconds);
+        }
+    }
+}
+
+fn toggle_telemetry(keyboard: Res<Input<KeyCode>>, mut telemetry: ResMut<Telemetry>) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        telemetry.open = !telemetry.open;
+    }
+}
+
+fn telemetry_window(
+    mut egui_context: ResMut<EguiContext>,
+    telemetry: Res<Telemetry>,
+    diagnostics: Res<Diagnostics>,
+    rapier_context: Res<RapierContext>,
+    index: Res<SpatialIndex>,
+    hearing_checks: Res<HearingChecks>,
+    sound_query: Query<(), With<SoundPoint>>,
+) {
+    if !telemetry.open {
+        return;
+    }
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or(0.)
+    };
+
+    egui::Window::new("Telemetry")
+        .anchor(egui::Align2::RIGHT_TOP, [-8., 8.])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Grid::new("telemetry").show(ui, |ui| {
+                let mut row = |label: &str, value: String| {
+                    ui.label(label);
+                    ui.monospace(value);
+                    ui.end_row();
+                };
+
+                row(
+                    "FPS",
+                    format!("{:.0}", average(FrameTimeDiagnosticsPlugin::FPS)),
+                );
+                row(
+                    "Frame time",
+                    format!(
+                        "{:.2} ms",
+                        average(FrameTimeDiagnosticsPlugin::FRAME_TIME) * 1000.
+                    ),
+                );
+                row("Sound dots", sound_query.iter().count().to_string());
+                row("Rigid bodies", rapier_context.bodies.len().to_string());
+                row("Colliders", rapier_context.colliders.len().to_string());
+                row("Spatial index", index.len().to_string());
+                row(
+                    "Hearing checks",
+                    format!(
+                        "{} of {}",
+                        hearing_checks.candidates, hearing_checks.without_index
+                    ),
+                );
+                row(
+                    "Fixed update",
+                    format!("{:.2} ms", average(FIXED_UPDATE_TIME)),
+                );
+                row("Update", format!("{:.2} ms", average(UPDATE_TIME)));
+                row(
+                    "Post update",
+                    format!("{:.2} ms", average(POST_UPDATE_TIME)),
+                );
+            });
+        });
+}
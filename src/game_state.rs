@@ -1,5 +1,53 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::input::{Action, Actions};
+
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub enum GameState {
     AssetLoading,
+    MainMenu,
     Playing,
 }
+
+/// Kept apart from `GameState` so unpausing doesn't re-run the
+/// `Playing` enter systems and respawn the world.
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum PauseState {
+    Running,
+    Paused,
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_loopless_state(PauseState::Running)
+            .add_system(toggle_pause.run_in_state(GameState::Playing))
+            .add_enter_system(PauseState::Paused, pause_physics)
+            .add_exit_system(PauseState::Paused, resume_physics);
+    }
+}
+
+fn toggle_pause(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    state: Res<CurrentState<PauseState>>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        let next = match state.0 {
+            PauseState::Running => PauseState::Paused,
+            PauseState::Paused => PauseState::Running,
+        };
+        commands.insert_resource(NextState(next));
+    }
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
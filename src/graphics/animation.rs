@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::{
+    game_state::PauseState,
+    timestep::{fixed_timestep, Visuals, FIXED_UPDATE, GAMEPLAY_SUBSTAGE},
+};
+
+/// Animations advance with the ticks, so they slow down with the rest of
+/// the game and a death animation is always done when the player respawns.
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep_system_set(
+            FIXED_UPDATE,
+            GAMEPLAY_SUBSTAGE,
+            ConditionSet::new()
+                .run_in_state(PauseState::Running)
+                .with_system(pick_animation_from_velocity)
+                .with_system(step_animations.after(pick_animation_from_velocity))
+                .into(),
+        );
+    }
+}
+
+/// Below this speed an entity counts as standing still.
+const WALK_SPEED_THRESHOLD: f32 = 2.;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum AnimationState {
+    Idle,
+    WalkUp,
+    WalkDown,
+    WalkLeft,
+    WalkRight,
+    EmitSound,
+    Death,
+}
+
+impl AnimationState {
+    /// Walking clip for moving in `direction`, picking the stronger axis.
+    fn walk(direction: Vec2) -> Self {
+        if direction.x.abs() >= direction.y.abs() {
+            if direction.x > 0. {
+                AnimationState::WalkRight
+            } else {
+                AnimationState::WalkLeft
+            }
+        } else if direction.y > 0. {
+            AnimationState::WalkUp
+        } else {
+            AnimationState::WalkDown
+        }
+    }
+}
+
+/// A run of frames in a sprite sheet.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Clip {
+    pub first: usize,
+    pub last: usize,
+    /// Seconds every frame is shown for.
+    pub frame_time: f32,
+    /// Loops forever when true, otherwise stops on the last frame.
+    pub looping: bool,
+}
+
+impl Clip {
+    pub fn looping(first: usize, last: usize, frame_time: f32) -> Self {
+        Self {
+            first,
+            last,
+            frame_time,
+            looping: true,
+        }
+    }
+
+    pub fn once(first: usize, last: usize, frame_time: f32) -> Self {
+        Self {
+            first,
+            last,
+            frame_time,
+            looping: false,
+        }
+    }
+}
+
+/// Plays named clips of a `TextureAtlasSprite`.
+///
+/// The state follows the entity's velocity, unless a one shot clip was
+/// started with `play_once`, which runs to its end first.
+/// `Death` is never left once started.
+#[derive(Component, Clone, Debug)]
+pub struct Animation {
+    clips: HashMap<AnimationState, Clip>,
+    state: AnimationState,
+    frame: usize,
+    timer: Timer,
+    one_shot: bool,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self::new([(AnimationState::Idle, Clip::looping(0, 0, 1.))])
+    }
+}
+
+impl Animation {
+    pub fn new(clips: impl IntoIterator<Item = (AnimationState, Clip)>) -> Self {
+        let clips: HashMap<AnimationState, Clip> = clips.into_iter().collect();
+        let idle = clips
+            .get(&AnimationState::Idle)
+            .copied()
+            .unwrap_or_else(|| Clip::looping(0, 0, 1.));
+
+        Self {
+            clips,
+            state: AnimationState::Idle,
+            frame: idle.first,
+            timer: Timer::from_seconds(idle.frame_time, true),
+            one_shot: false,
+        }
+    }
+
+    pub fn state(&self) -> AnimationState {
+        self.state
+    }
+
+    /// Plays `state` from its first frame and keeps it until it's done.
+    pub fn play_once(&mut self, state: AnimationState) {
+        if self.state != AnimationState::Death {
+            self.switch_to(state);
+            self.one_shot = true;
+        }
+    }
+
+    /// Switches to `state` if nothing more important is playing.
+    pub fn play(&mut self, state: AnimationState) {
+        let state = self.resolve(state);
+        if !self.one_shot && self.state != AnimationState::Death && self.state != state {
+            self.switch_to(state);
+        }
+    }
+
+    /// Goes back to idling, even from `Death`.
+    pub fn reset(&mut self) {
+        self.one_shot = false;
+        self.switch_to(AnimationState::Idle);
+    }
+
+    /// Entities without a clip for a state just keep idling.
+    fn resolve(&self, state: AnimationState) -> AnimationState {
+        if self.clips.contains_key(&state) {
+            state
+        } else {
+            AnimationState::Idle
+        }
+    }
+
+    fn switch_to(&mut self, state: AnimationState) {
+        let state = self.resolve(state);
+        let clip = self.clip(state);
+
+        self.state = state;
+        self.frame = clip.first;
+        self.timer = Timer::from_seconds(clip.frame_time, true);
+    }
+
+    fn clip(&self, state: AnimationState) -> Clip {
+        self.clips
+            .get(&state)
+            .copied()
+            .unwrap_or_else(|| Clip::looping(0, 0, 1.))
+    }
+
+    fn tick(&mut self, delta: std::time::Duration) -> usize {
+        let clip = self.clip(self.state);
+        self.timer.tick(delta);
+
+        for _ in 0..self.timer.times_finished() {
+            if self.frame < clip.last {
+                self.frame += 1;
+            } else if clip.looping {
+                self.frame = clip.first;
+            } else if self.one_shot && self.state != AnimationState::Death {
+                self.one_shot = false;
+                self.switch_to(AnimationState::Idle);
+                break;
+            }
+        }
+
+        self.frame
+    }
+}
+
+fn pick_animation_from_velocity(mut query: Query<(&mut Animation, &Velocity)>) {
+    for (mut animation, velocity) in query.iter_mut() {
+        let state = if velocity.linvel.length() > WALK_SPEED_THRESHOLD {
+            AnimationState::walk(velocity.linvel)
+        } else {
+            AnimationState::Idle
+        };
+        animation.play(state);
+    }
+}
+
+fn step_animations(
+    mut query: Query<(Entity, &mut Animation, Option<&Visuals>)>,
+    mut sprite_query: Query<&mut TextureAtlasSprite>,
+) {
+    for (entity, mut animation, visuals) in query.iter_mut() {
+        let frame = animation.tick(fixed_timestep());
+        if let Ok(mut sprite) = sprite_query.get_mut(Visuals::of(entity, visuals)) {
+            if sprite.index != frame {
+                sprite.index = frame;
+            }
+        }
+    }
+}
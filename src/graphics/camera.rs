@@ -1,7 +1,12 @@
 use bevy::prelude::*;
+#[cfg(feature = "inspector")]
 use bevy_inspector_egui::prelude::*;
 use bevy_pixel_camera::PixelCameraBundle;
 
+use super::camera_shake::CameraShake;
+use super::camera_zoom::{CameraZoom, DEFAULT_ZOOM};
+use crate::timestep::Interpolated;
+
 #[derive(Component)]
 pub struct LerpSpeed(pub f32);
 
@@ -12,42 +17,54 @@ impl Default for LerpSpeed {
 }
 
 pub fn spawn_camera(mut commands: Commands) {
-    let camera = PixelCameraBundle::from_zoom(2);
+    let camera = PixelCameraBundle::from_zoom(DEFAULT_ZOOM);
     commands
         .spawn_bundle(camera)
         .insert(CurrentCameraAnchorEntityId(None))
         .insert(LerpSpeed(3.0))
+        .insert(CameraShake::default())
+        .insert(CameraZoom::default())
         .insert(Name::new("Pixel Camera"));
 }
 
-#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Default, Inspectable)]
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
 pub struct CameraAnchor(pub i32);
 
-#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Default, Inspectable)]
+#[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
 pub struct CurrentCameraAnchorEntityId(pub Option<u32>);
 
 #[allow(clippy::type_complexity)]
 pub fn camera_follow_anchor(
-    anchor_query: Query<(Entity, &Transform, &CameraAnchor)>,
+    anchor_query: Query<(Entity, &Transform, &CameraAnchor, Option<&Interpolated>)>,
     mut camera_query: Query<
         (&mut CurrentCameraAnchorEntityId, &mut Transform, &LerpSpeed),
         (Without<CameraAnchor>, With<Camera>),
     >,
     time: Res<Time>,
 ) {
-    if let Some((entity, anchor_transform, _)) = anchor_query
+    if let Some((entity, anchor_transform, _, interpolated)) = anchor_query
         .iter()
-        .max_by(|(_, _, &anchor_a), (_, _, &anchor_b)| anchor_a.0.cmp(&anchor_b.0))
+        .max_by(|(_, _, &anchor_a, _), (_, _, &anchor_b, _)| anchor_a.0.cmp(&anchor_b.0))
     {
-        let (mut current_anchor_id, mut camera_transform, lerp_speed) = camera_query.single_mut();
+        // Where the anchor is drawn, not where it was on the last tick
+        let offset = interpolated.map_or(Vec2::ZERO, Interpolated::offset);
+        let target = anchor_transform.translation + offset.extend(0.);
+
+        // The level and its anchors spawn during loading, before the camera
+        let (mut current_anchor_id, mut camera_transform, lerp_speed) =
+            match camera_query.get_single_mut() {
+                Ok(camera) => camera,
+                Err(_) => return,
+            };
 
         if current_anchor_id.0.is_some() {
-            camera_transform.translation = camera_transform.translation.lerp(
-                anchor_transform.translation,
-                lerp_speed.0 * time.delta_seconds(),
-            );
+            camera_transform.translation = camera_transform
+                .translation
+                .lerp(target, lerp_speed.0 * time.delta_seconds());
         } else {
-            camera_transform.translation = anchor_transform.translation;
+            camera_transform.translation = target;
         }
 
         current_anchor_id.0 = Some(entity.id());
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::prelude::*;
+
+use super::camera::camera_follow_anchor;
+
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CameraShakeEvent>()
+            .add_system(receive_camera_shake_events)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                remove_camera_shake.before(camera_follow_anchor),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_camera_shake
+                    .after(camera_follow_anchor)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Request to shake the camera.
+///
+/// `trauma` is added to the camera's current trauma and clamped to 1.0,
+/// so a few small events can stack into a big shake.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CameraShakeEvent {
+    pub trauma: f32,
+}
+
+impl CameraShakeEvent {
+    /// The player clapping or anything else making a loud noise.
+    pub const LOUD_SOUND: Self = Self { trauma: 0.3 };
+    /// A mutant got its hands on the player.
+    pub const PLAYER_CAUGHT: Self = Self { trauma: 0.8 };
+}
+
+/// Trauma based screen shake for the pixel camera.
+///
+/// The offset grows with the square of the trauma, so small amounts barely
+/// move the camera while big hits are very noticeable.
+/// Offsets are rounded to whole pixels so the image stays crisp.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
+pub struct CameraShake {
+    /// Current trauma in the range [0, 1].
+    pub trauma: f32,
+    /// How much trauma is lost per second.
+    pub decay: f32,
+    /// Offset in pixels when trauma is at 1.0.
+    pub max_offset: f32,
+    /// How fast the shake oscillates.
+    pub frequency: f32,
+    #[cfg_attr(feature = "inspector", inspectable(ignore))]
+    applied_offset: Vec2,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.,
+            decay: 1.5,
+            max_offset: 6.,
+            frequency: 25.,
+            applied_offset: Vec2::ZERO,
+        }
+    }
+}
+
+fn receive_camera_shake_events(
+    mut events: EventReader<CameraShakeEvent>,
+    mut camera_query: Query<&mut CameraShake>,
+) {
+    for event in events.iter() {
+        for mut shake in camera_query.iter_mut() {
+            shake.trauma = (shake.trauma + event.trauma).clamp(0., 1.);
+        }
+    }
+}
+
+/// Takes out last frame's offset so the anchor following works on
+/// the real camera position.
+fn remove_camera_shake(mut camera_query: Query<(&mut Transform, &mut CameraShake)>) {
+    for (mut transform, mut shake) in camera_query.iter_mut() {
+        transform.translation -= shake.applied_offset.extend(0.);
+        shake.applied_offset = Vec2::ZERO;
+    }
+}
+
+fn apply_camera_shake(
+    mut camera_query: Query<(&mut Transform, &mut CameraShake)>,
+    time: Res<Time>,
+) {
+    for (mut transform, mut shake) in camera_query.iter_mut() {
+        if shake.trauma <= 0. {
+            continue;
+        }
+
+        let amount = shake.trauma * shake.trauma * shake.max_offset;
+        let t = time.seconds_since_startup() as f32 * shake.frequency;
+
+        // Two sines with unrelated frequencies are a cheap stand in for noise.
+        let offset = Vec2::new(
+            (t.sin() + (t * 2.3 + 1.7).sin()) / 2.,
+            ((t * 1.3 + 0.5).sin() + (t * 3.1).sin()) / 2.,
+        ) * amount;
+
+        // Snap to the pixel grid so the sprites don't shimmer.
+        let offset = offset.round();
+
+        transform.translation += offset.extend(0.);
+        shake.applied_offset = offset;
+        shake.trauma = (shake.trauma - shake.decay * time.delta_seconds()).max(0.);
+    }
+}
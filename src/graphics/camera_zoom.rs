@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::prelude::*;
+use bevy_pixel_camera::PixelProjection;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::{Action, Actions},
+    utils::ldtk_fields::level_field,
+};
+
+pub struct CameraZoomPlugin;
+
+impl Plugin for CameraZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ZoomSetting>()
+            .init_resource::<LevelZoom>()
+            .add_system(change_zoom_setting)
+            .add_system(read_level_zoom)
+            .add_system(
+                pick_camera_zoom
+                    .after(change_zoom_setting)
+                    .after(read_level_zoom),
+            )
+            .add_system(step_camera_zoom.after(pick_camera_zoom));
+    }
+}
+
+pub const DEFAULT_ZOOM: i32 = 2;
+pub const MIN_ZOOM: i32 = 1;
+pub const MAX_ZOOM: i32 = 6;
+
+/// The smallest area of the world, in pixels, the auto zoom will show.
+const AUTO_ZOOM_MIN_VIEW: Vec2 = Vec2::new(480., 270.);
+
+/// Identifier of the optional integer level field that sets the zoom of a level.
+const LEVEL_ZOOM_FIELD: &str = "Zoom";
+
+/// How the player wants the zoom to be chosen.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ZoomSetting {
+    /// Use the level's zoom if it has one, otherwise fit the window.
+    #[default]
+    Auto,
+    /// Always use this zoom.
+    Fixed(i32),
+}
+
+/// Zoom requested by the currently spawned level, if any.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct LevelZoom(pub Option<i32>);
+
+/// Drives the integer zoom of a `PixelProjection`.
+///
+/// The zoom never jumps straight to the target, it moves one step at a time
+/// so the transition reads as smooth while every frame stays pixel perfect.
+#[derive(Component, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
+pub struct CameraZoom {
+    pub target: i32,
+    #[cfg_attr(feature = "inspector", inspectable(ignore))]
+    pub step_timer: Timer,
+}
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        Self {
+            target: DEFAULT_ZOOM,
+            step_timer: Timer::from_seconds(0.08, true),
+        }
+    }
+}
+
+fn change_zoom_setting(
+    mut setting: ResMut<ZoomSetting>,
+    actions: Res<Actions>,
+    camera_query: Query<&PixelProjection, With<CameraZoom>>,
+) {
+    let current = match *setting {
+        ZoomSetting::Fixed(zoom) => zoom,
+        ZoomSetting::Auto => camera_query
+            .get_single()
+            .map_or(DEFAULT_ZOOM, |projection| projection.zoom),
+    };
+
+    if actions.just_pressed(Action::ZoomIn) {
+        *setting = ZoomSetting::Fixed((current + 1).min(MAX_ZOOM));
+    }
+    if actions.just_pressed(Action::ZoomOut) {
+        *setting = ZoomSetting::Fixed((current - 1).max(MIN_ZOOM));
+    }
+    if actions.just_pressed(Action::ResetZoom) {
+        *setting = ZoomSetting::Auto;
+    }
+}
+
+fn read_level_zoom(
+    mut level_events: EventReader<LevelEvent>,
+    level_query: Query<&Handle<LdtkLevel>>,
+    levels: Res<Assets<LdtkLevel>>,
+    mut level_zoom: ResMut<LevelZoom>,
+) {
+    for level_event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = level_event {
+            let zoom = level_query
+                .iter()
+                .filter_map(|handle| levels.get(handle))
+                .find(|level| &level.level.iid == iid)
+                .and_then(|level| level_field(&level.level, LEVEL_ZOOM_FIELD))
+                .and_then(|value| match value {
+                    FieldValue::Int(zoom) => *zoom,
+                    _ => None,
+                });
+
+            level_zoom.0 = zoom.map(|zoom| zoom.clamp(MIN_ZOOM, MAX_ZOOM));
+        }
+    }
+}
+
+/// Largest zoom that still shows at least `AUTO_ZOOM_MIN_VIEW` of the world.
+fn auto_zoom(window_width: f32, window_height: f32) -> i32 {
+    let fit = (window_width / AUTO_ZOOM_MIN_VIEW.x).min(window_height / AUTO_ZOOM_MIN_VIEW.y);
+    (fit.floor() as i32).clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+fn pick_camera_zoom(
+    setting: Res<ZoomSetting>,
+    level_zoom: Res<LevelZoom>,
+    windows: Res<Windows>,
+    mut camera_query: Query<&mut CameraZoom>,
+) {
+    let target = match (*setting, level_zoom.0) {
+        (ZoomSetting::Fixed(zoom), _) => zoom,
+        (ZoomSetting::Auto, Some(zoom)) => zoom,
+        (ZoomSetting::Auto, None) => match windows.get_primary() {
+            Some(window) => auto_zoom(window.width(), window.height()),
+            None => DEFAULT_ZOOM,
+        },
+    };
+
+    for mut zoom in camera_query.iter_mut() {
+        if zoom.target != target {
+            zoom.target = target;
+        }
+    }
+}
+
+fn step_camera_zoom(
+    mut camera_query: Query<(&mut PixelProjection, &mut CameraZoom)>,
+    time: Res<Time>,
+) {
+    for (mut projection, mut zoom) in camera_query.iter_mut() {
+        if projection.zoom == zoom.target {
+            zoom.step_timer.reset();
+            continue;
+        }
+
+        zoom.step_timer.tick(time.delta());
+        if zoom.step_timer.just_finished() {
+            projection.zoom += (zoom.target - projection.zoom).signum();
+        }
+    }
+}
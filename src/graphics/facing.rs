@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use iyes_loopless::prelude::*;
+
+use crate::timestep::{Visuals, FIXED_UPDATE, GAMEPLAY_SUBSTAGE};
+
+/// Turns characters the way they move.
+///
+/// Runs on the fixed timestep, gameplay reads `Facing::direction`.
+pub struct FacingPlugin;
+
+impl Plugin for FacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep_system(FIXED_UPDATE, GAMEPLAY_SUBSTAGE, face_movement_direction);
+    }
+}
+
+/// Sideways speed needed before a sprite turns around,
+/// so it doesn't flicker when standing still or moving straight up.
+const TURN_SPEED_THRESHOLD: f32 = 1.;
+
+/// Flips a sprite sheet so it faces the way the entity is moving.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Facing {
+    /// Which way the character looks in the sprite sheet itself.
+    pub art_faces_left: bool,
+    /// Which way the character looks right now, it starts out like the art.
+    facing_left: bool,
+}
+
+impl Facing {
+    pub fn art_faces_left() -> Self {
+        Self {
+            art_faces_left: true,
+            facing_left: true,
+        }
+    }
+
+    /// The way the character looks, left or right.
+    pub fn direction(&self) -> Vec2 {
+        if self.facing_left {
+            Vec2::NEG_X
+        } else {
+            Vec2::X
+        }
+    }
+}
+
+pub fn face_movement_direction(
+    mut query: Query<(Entity, &mut Facing, &Velocity, Option<&Visuals>)>,
+    mut sprite_query: Query<&mut TextureAtlasSprite>,
+) {
+    for (entity, mut facing, velocity, visuals) in query.iter_mut() {
+        if velocity.linvel.x.abs() < TURN_SPEED_THRESHOLD {
+            continue;
+        }
+
+        let moving_left = velocity.linvel.x < 0.;
+        if facing.facing_left != moving_left {
+            facing.facing_left = moving_left;
+        }
+
+        let flip_x = moving_left != facing.art_faces_left;
+        if let Ok(mut sprite) = sprite_query.get_mut(Visuals::of(entity, visuals)) {
+            if sprite.flip_x != flip_x {
+                sprite.flip_x = flip_x;
+            }
+        }
+    }
+}
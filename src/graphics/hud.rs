@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+use crate::player::{NoiseBudget, Player};
+
+const NOISE_BAR_WIDTH: f32 = 120.;
+const NOISE_BAR_HEIGHT: f32 = 10.;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct NoiseBar;
+
+/// Spawns the bar showing the player's `NoiseBudget`.
+pub fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(NOISE_BAR_WIDTH), Val::Px(NOISE_BAR_HEIGHT)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(16.),
+                    bottom: Val::Px(16.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: Color::rgba(1., 1., 1., 0.15).into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Noise Budget"))
+        .with_children(|bar| {
+            bar.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    ..Default::default()
+                },
+                color: Color::WHITE.into(),
+                ..Default::default()
+            })
+            .insert(NoiseBar);
+        });
+}
+
+pub fn update_noise_bar(
+    budget_query: Query<&NoiseBudget, (With<Player>, Changed<NoiseBudget>)>,
+    mut bar_query: Query<(&mut Style, &mut UiColor), With<NoiseBar>>,
+) {
+    if let Ok(budget) = budget_query.get_single() {
+        for (mut style, mut color) in bar_query.iter_mut() {
+            style.size.width = Val::Percent(budget.fraction() * 100.);
+            // Dim the bar while the player can't make a sound
+            color.0 = if budget.cooldown.finished() {
+                Color::WHITE
+            } else {
+                Color::GRAY
+            };
+        }
+    }
+}
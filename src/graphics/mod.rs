@@ -1 +1,35 @@
+use bevy::prelude::*;
+use bevy_pixel_camera::PixelCameraPlugin;
+use iyes_loopless::prelude::*;
+
+use crate::{game_state::GameState, timestep::interpolate_positions};
+
+pub mod animation;
 pub mod camera;
+pub mod camera_shake;
+pub mod camera_zoom;
+pub mod facing;
+pub mod hud;
+pub mod y_sort;
+
+/// The camera and the HUD, the parts of the graphics that need a window.
+///
+/// Animations, facing and y-sorting only touch sprites,
+/// they're part of the `GameplayPlugin` so headless runs keep them.
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(Color::BLACK))
+            .add_plugin(PixelCameraPlugin)
+            .add_plugin(camera_shake::CameraShakePlugin)
+            .add_plugin(camera_zoom::CameraZoomPlugin)
+            .add_enter_system(GameState::Playing, camera::spawn_camera)
+            .add_enter_system(GameState::Playing, hud::spawn_hud)
+            .add_system(hud::update_noise_bar)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                camera::camera_follow_anchor.after(interpolate_positions),
+            );
+    }
+}
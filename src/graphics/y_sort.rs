@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+pub struct YSortPlugin;
+
+impl Plugin for YSortPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            y_sort.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// How much depth one pixel of height is worth.
+///
+/// Keeps a few hundred pixels of level height well within the gap between
+/// two LDtk layers, so sorted sprites never end up behind the tiles.
+const Y_SORT_SCALE: f32 = 0.0001;
+
+/// Draws the entity in front of everything above it on the screen,
+/// so characters passing each other overlap the right way round.
+///
+/// Meant for the entities of the LDtk `Entities` layer, which all share
+/// the same parent and so the same space for their local transforms.
+#[derive(Copy, Clone, PartialEq, Debug, Default, Component)]
+pub struct YSort {
+    /// The depth LDtk spawned the entity at, sorting is done relative to it.
+    base_z: Option<f32>,
+}
+
+fn y_sort(mut query: Query<(&mut Transform, &mut YSort), Changed<Transform>>) {
+    for (mut transform, mut y_sort) in query.iter_mut() {
+        let base_z = *y_sort.base_z.get_or_insert(transform.translation.z);
+        let z = base_z - transform.translation.y * Y_SORT_SCALE;
+
+        // Writing an unchanged z would mark it `Changed` and sort it again every frame
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
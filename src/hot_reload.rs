@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    game_state::GameState,
+    player::{PendingPlayerPosition, Player},
+    resources::{CurrentLevel, WorldAssets},
+};
+
+/// Respawns the LDtk world in place whenever `Map.ldtk` changes on disk.
+///
+/// Only does something when the asset server is watching for changes,
+/// which the `dev` feature turns on.
+pub struct HotReloadPlugin;
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(respawn_world_on_map_change.run_in_state(GameState::Playing));
+    }
+}
+
+/// Tears down the old world and spawns a fresh one from the reloaded asset.
+///
+/// Walls come back as newly added `Wall` entities, so `spawn_wall_collision`
+/// regenerates the merged colliders without any extra work.
+fn respawn_world_on_map_change(
+    mut commands: Commands,
+    mut ldtk_events: EventReader<AssetEvent<LdtkAsset>>,
+    assets: Res<WorldAssets>,
+    world_query: Query<(Entity, &Handle<LdtkAsset>)>,
+    player_query: Query<&Transform, With<Player>>,
+    current_level: Res<CurrentLevel>,
+) {
+    let map_changed = ldtk_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle } => *handle == assets.map,
+        _ => false,
+    });
+
+    if !map_changed {
+        return;
+    }
+
+    info!("Map.ldtk changed, respawning world");
+    respawn_world_in_place(
+        &mut commands,
+        &world_query,
+        &player_query,
+        &current_level,
+        &assets.map,
+    );
+}
+
+/// Like `respawn_world`, but the player stays where they were.
+pub fn respawn_world_in_place(
+    commands: &mut Commands,
+    world_query: &Query<(Entity, &Handle<LdtkAsset>)>,
+    player_query: &Query<&Transform, With<Player>>,
+    current_level: &CurrentLevel,
+    map: &Handle<LdtkAsset>,
+) {
+    if let (Ok(player_transform), Some(level_iid)) =
+        (player_query.get_single(), current_level.0.clone())
+    {
+        commands.insert_resource(PendingPlayerPosition {
+            level_iid,
+            translation: player_transform.translation,
+        });
+    }
+
+    respawn_world(commands, world_query, map);
+}
+
+/// Despawns the world spawned from `map` and spawns it again from scratch,
+/// with the level in `LevelSelection`.
+pub fn respawn_world(
+    commands: &mut Commands,
+    world_query: &Query<(Entity, &Handle<LdtkAsset>)>,
+    map: &Handle<LdtkAsset>,
+) {
+    for (world_entity, handle) in world_query.iter() {
+        if handle == map {
+            commands.entity(world_entity).despawn_recursive();
+        }
+    }
+
+    commands.spawn_bundle(LdtkWorldBundle {
+        ldtk_handle: map.clone(),
+        ..Default::default()
+    });
+}
@@ -0,0 +1,407 @@
+use bevy::asset::{AssetServerSettings, FileAssetIo};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy_pixel_camera::PixelProjection;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::timestep::{FIXED_UPDATE, INPUT_SUBSTAGE};
+
+/// Where the player's bindings are read from, relative to the asset folder.
+pub const INPUT_CONFIG_PATH: &str = "input.json";
+
+/// Turns raw keyboard and gamepad input into game `Actions`.
+///
+/// Gameplay systems should only ever read `Actions`,
+/// so the bindings can change without touching them.
+/// Systems in `FIXED_UPDATE` read the `TickActions` instead.
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = input_config_path(app.world.get_resource::<AssetServerSettings>());
+        app.insert_resource(InputConfig::load(&path))
+            .init_resource::<Actions>()
+            .init_resource::<TickActions>()
+            .init_resource::<PendingPresses>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_actions.after(InputSystem))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_cursor_world_position.after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                collect_presses
+                    .after(update_actions)
+                    .after(update_cursor_world_position),
+            )
+            .add_fixed_timestep_system(FIXED_UPDATE, INPUT_SUBSTAGE, latch_tick_actions);
+    }
+}
+
+/// `INPUT_CONFIG_PATH` in the folder the `AssetServer` loads from,
+/// so it's found whichever directory the game is started from.
+pub fn input_config_path(settings: Option<&AssetServerSettings>) -> PathBuf {
+    let asset_folder = settings
+        .map(|settings| settings.asset_folder.clone())
+        .unwrap_or_else(|| AssetServerSettings::default().asset_folder);
+
+    FileAssetIo::get_base_path()
+        .join(asset_folder)
+        .join(INPUT_CONFIG_PATH)
+}
+
+/// Buttons the player can press, movement is read from `Actions::movement`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MakeSound,
+    /// Throws a narrow echo towards the cursor.
+    AimedSound,
+    /// Picks up the closest item, or throws the one being carried.
+    Throw,
+    Sneak,
+    Pause,
+    ZoomIn,
+    ZoomOut,
+    /// Goes back to the automatic zoom.
+    ResetZoom,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::MakeSound,
+        Action::AimedSound,
+        Action::Throw,
+        Action::Sneak,
+        Action::Pause,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ResetZoom,
+    ];
+}
+
+/// The keys, mouse buttons and gamepad buttons bound to one action.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Binding {
+    pub keys: Vec<KeyCode>,
+    pub mouse_buttons: Vec<MouseButton>,
+    pub gamepad_buttons: Vec<GamepadButtonType>,
+}
+
+impl Binding {
+    fn new(keys: &[KeyCode], gamepad_buttons: &[GamepadButtonType]) -> Self {
+        Self {
+            keys: keys.to_vec(),
+            mouse_buttons: Vec::new(),
+            gamepad_buttons: gamepad_buttons.to_vec(),
+        }
+    }
+
+    fn with_mouse(mut self, mouse_buttons: &[MouseButton]) -> Self {
+        self.mouse_buttons = mouse_buttons.to_vec();
+        self
+    }
+}
+
+/// Which gamepad stick moves the player.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+impl Stick {
+    fn axes(&self) -> (GamepadAxisType, GamepadAxisType) {
+        match self {
+            Stick::Left => (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+            Stick::Right => (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        }
+    }
+}
+
+/// The player's input bindings, read from json at `input_config_path`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    pub move_up: Binding,
+    pub move_down: Binding,
+    pub move_left: Binding,
+    pub move_right: Binding,
+    pub move_stick: Stick,
+    /// Stick deflection below this is ignored.
+    pub stick_dead_zone: f32,
+    pub make_sound: Binding,
+    pub aimed_sound: Binding,
+    pub throw: Binding,
+    pub sneak: Binding,
+    pub pause: Binding,
+    pub zoom_in: Binding,
+    pub zoom_out: Binding,
+    pub reset_zoom: Binding,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            move_up: Binding::new(&[KeyCode::W, KeyCode::Up], &[GamepadButtonType::DPadUp]),
+            move_down: Binding::new(&[KeyCode::S, KeyCode::Down], &[GamepadButtonType::DPadDown]),
+            move_left: Binding::new(&[KeyCode::A, KeyCode::Left], &[GamepadButtonType::DPadLeft]),
+            move_right: Binding::new(
+                &[KeyCode::D, KeyCode::Right],
+                &[GamepadButtonType::DPadRight],
+            ),
+            move_stick: Stick::Left,
+            stick_dead_zone: 0.15,
+            make_sound: Binding::new(&[KeyCode::Space], &[GamepadButtonType::South]),
+            aimed_sound: Binding::new(&[], &[]).with_mouse(&[MouseButton::Left]),
+            throw: Binding::new(&[KeyCode::E], &[GamepadButtonType::West])
+                .with_mouse(&[MouseButton::Right]),
+            sneak: Binding::new(&[KeyCode::LShift], &[GamepadButtonType::LeftTrigger]),
+            pause: Binding::new(&[KeyCode::Escape], &[GamepadButtonType::Start]),
+            zoom_in: Binding::new(&[KeyCode::Equals], &[]),
+            zoom_out: Binding::new(&[KeyCode::Minus], &[]),
+            reset_zoom: Binding::new(&[KeyCode::Key0], &[]),
+        }
+    }
+}
+
+impl InputConfig {
+    /// Reads the bindings from `path`, falling back to the defaults
+    /// if the file is missing or broken.
+    pub fn load(path: &Path) -> Self {
+        match Self::try_load(path) {
+            Ok(config) => config,
+            Err(error) => {
+                info!("Using default input bindings: {}", error);
+                Self::default()
+            }
+        }
+    }
+
+    fn try_load(path: &Path) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn binding(&self, action: Action) -> &Binding {
+        match action {
+            Action::MakeSound => &self.make_sound,
+            Action::AimedSound => &self.aimed_sound,
+            Action::Throw => &self.throw,
+            Action::Sneak => &self.sneak,
+            Action::Pause => &self.pause,
+            Action::ZoomIn => &self.zoom_in,
+            Action::ZoomOut => &self.zoom_out,
+            Action::ResetZoom => &self.reset_zoom,
+        }
+    }
+}
+
+/// What the player wants to do this frame.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Actions {
+    /// Direction to move in, with a length of at most 1.
+    /// Gamepad sticks give anything in between for analog movement.
+    pub movement: Vec2,
+    /// Where the mouse cursor points to in the world, if it's in the window.
+    pub cursor_world_position: Option<Vec2>,
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl Actions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Overrides the state of an action, for things that aren't real input
+    /// like replays and the console.
+    pub fn set(&mut self, action: Action, pressed: bool, just_pressed: bool) {
+        if pressed {
+            self.pressed.insert(action);
+        } else {
+            self.pressed.remove(&action);
+        }
+        if just_pressed {
+            self.just_pressed.insert(action);
+        } else {
+            self.just_pressed.remove(&action);
+        }
+    }
+}
+
+/// The `Actions` a tick of `FIXED_UPDATE` sees.
+///
+/// A frame can run several ticks or none at all, so presses are kept
+/// until the next tick and only that tick sees them as just pressed.
+/// An action pressed and released again between two ticks still counts
+/// as pressed for that tick.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TickActions(pub Actions);
+
+impl Deref for TickActions {
+    type Target = Actions;
+
+    fn deref(&self) -> &Actions {
+        &self.0
+    }
+}
+
+/// Actions that were just pressed since the last tick.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PendingPresses(HashSet<Action>);
+
+struct RawInput<'a> {
+    keyboard: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    gamepad_buttons: &'a Input<GamepadButton>,
+    gamepads: &'a Gamepads,
+}
+
+impl<'a> RawInput<'a> {
+    fn pressed(&self, binding: &Binding) -> bool {
+        binding.keys.iter().any(|&key| self.keyboard.pressed(key))
+            || binding
+                .mouse_buttons
+                .iter()
+                .any(|&button| self.mouse.pressed(button))
+            || self.any_gamepad(binding, |button| self.gamepad_buttons.pressed(button))
+    }
+
+    fn just_pressed(&self, binding: &Binding) -> bool {
+        binding
+            .keys
+            .iter()
+            .any(|&key| self.keyboard.just_pressed(key))
+            || binding
+                .mouse_buttons
+                .iter()
+                .any(|&button| self.mouse.just_pressed(button))
+            || self.any_gamepad(binding, |button| self.gamepad_buttons.just_pressed(button))
+    }
+
+    fn any_gamepad(&self, binding: &Binding, check: impl Fn(GamepadButton) -> bool) -> bool {
+        self.gamepads.iter().any(|&gamepad| {
+            binding
+                .gamepad_buttons
+                .iter()
+                .any(|&button_type| check(GamepadButton::new(gamepad, button_type)))
+        })
+    }
+}
+
+pub fn update_actions(
+    mut actions: ResMut<Actions>,
+    config: Res<InputConfig>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+) {
+    let raw = RawInput {
+        keyboard: &keyboard,
+        mouse: &mouse,
+        gamepad_buttons: &gamepad_buttons,
+        gamepads: &gamepads,
+    };
+
+    let mut movement = Vec2::ZERO;
+    if raw.pressed(&config.move_up) {
+        movement.y += 1.;
+    }
+    if raw.pressed(&config.move_down) {
+        movement.y -= 1.;
+    }
+    if raw.pressed(&config.move_right) {
+        movement.x += 1.;
+    }
+    if raw.pressed(&config.move_left) {
+        movement.x -= 1.;
+    }
+
+    let (x_axis, y_axis) = config.move_stick.axes();
+    for &gamepad in gamepads.iter() {
+        let stick = Vec2::new(
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, x_axis))
+                .unwrap_or(0.),
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, y_axis))
+                .unwrap_or(0.),
+        );
+        if stick.length() > config.stick_dead_zone {
+            movement += stick;
+        }
+    }
+
+    actions.movement = movement.clamp_length_max(1.);
+
+    for action in Action::ALL {
+        let binding = config.binding(action);
+        actions.set(action, raw.pressed(binding), raw.just_pressed(binding));
+    }
+}
+
+/// Projects the cursor through the pixel camera into world coordinates.
+pub fn update_cursor_world_position(
+    mut actions: ResMut<Actions>,
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PixelProjection>>,
+) {
+    actions.cursor_world_position = None;
+
+    let (camera, camera_transform) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    if let Some(cursor) = window.cursor_position() {
+        let window_size = Vec2::new(window.width(), window.height());
+        let ndc = (cursor / window_size) * 2. - Vec2::ONE;
+        let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+        let world = ndc_to_world.project_point3(ndc.extend(-1.));
+
+        actions.cursor_world_position = Some(world.truncate());
+    }
+}
+
+pub fn collect_presses(actions: Res<Actions>, mut pending: ResMut<PendingPresses>) {
+    for action in Action::ALL {
+        if actions.just_pressed(action) {
+            pending.0.insert(action);
+        }
+    }
+}
+
+pub fn latch_tick_actions(
+    actions: Res<Actions>,
+    mut pending: ResMut<PendingPresses>,
+    mut tick_actions: ResMut<TickActions>,
+) {
+    let mut latched = actions.clone();
+    for action in Action::ALL {
+        let just_pressed = pending.0.remove(&action);
+        latched.set(
+            action,
+            latched.pressed(action) || just_pressed,
+            just_pressed,
+        );
+    }
+
+    tick_actions.0 = latched;
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    checkpoint::Dying,
+    game_state::PauseState,
+    graphics::y_sort::YSort,
+    player::Player,
+    resources::{CompletedLevels, CurrentLevel, WorldAssets},
+    spatial_index::{SpatialIndex, SpatialIndexed},
+    timestep::{FIXED_UPDATE, GAMEPLAY_SUBSTAGE},
+};
+
+/// Finishes the level when the player reaches its exit.
+///
+/// The level is added to `CompletedLevels` and the next level of the
+/// LDtk world is selected. The last level has nowhere to go,
+/// finishing it only marks it completed.
+pub struct LevelExitPlugin;
+
+impl Plugin for LevelExitPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep_system_set(
+            FIXED_UPDATE,
+            GAMEPLAY_SUBSTAGE,
+            ConditionSet::new()
+                .run_in_state(PauseState::Running)
+                .with_system(reach_level_exit)
+                .into(),
+        );
+    }
+}
+
+/// How close the player has to get to the exit to leave the level.
+const EXIT_RADIUS: f32 = 12.;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct LevelExit;
+
+#[derive(Bundle, Default, LdtkEntity)]
+pub struct LevelExitBundle {
+    level_exit: LevelExit,
+    #[with(exit_sprite)]
+    #[bundle]
+    sprite_bundle: SpriteBundle,
+    y_sort: YSort,
+    spatial_indexed: SpatialIndexed,
+}
+
+fn exit_sprite(_: EntityInstance) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: Color::rgb(0.9, 0.8, 0.3),
+            custom_size: Some(Vec2::new(16., 16.)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reach_level_exit(
+    mut commands: Commands,
+    index: Res<SpatialIndex>,
+    current_level: Res<CurrentLevel>,
+    mut completed_levels: ResMut<CompletedLevels>,
+    level_selection: Res<LevelSelection>,
+    world_assets: Res<WorldAssets>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    player_query: Query<Entity, (With<Player>, Without<Dying>)>,
+    exit_query: Query<(), With<LevelExit>>,
+) {
+    let player_position = match player_query
+        .get_single()
+        .ok()
+        .and_then(|player| index.position(player))
+    {
+        Some(position) => position,
+        None => return,
+    };
+    let level_iid = match &current_level.0 {
+        Some(level_iid) => level_iid,
+        None => return,
+    };
+
+    let reached = index
+        .in_radius(player_position, EXIT_RADIUS)
+        .any(|entity| exit_query.contains(entity));
+    if !reached {
+        return;
+    }
+
+    if completed_levels.0.insert(level_iid.clone()) {
+        info!("Finished level {}", level_iid);
+    }
+
+    let next_level = ldtk_assets.get(&world_assets.map).and_then(|ldtk_asset| {
+        let levels = &ldtk_asset.project.levels;
+        let index = levels.iter().position(|level| level.iid == *level_iid)?;
+        levels.get(index + 1)
+    });
+
+    if let Some(next_level) = next_level {
+        let selection = LevelSelection::Iid(next_level.iid.clone());
+        // The player stands on the exit until the next level has spawned
+        if *level_selection != selection {
+            commands.insert_resource(selection);
+        }
+    }
+}
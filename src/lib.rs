@@ -0,0 +1,276 @@
+use bevy::{
+    app::PluginGroupBuilder,
+    asset::{AssetPlugin, AssetServerSettings},
+    audio::AudioPlugin,
+    hierarchy::HierarchyPlugin,
+    input::InputPlugin,
+    prelude::*,
+    render::texture::{ImagePlugin, ImageSettings},
+    time::TimeSystem,
+    transform::TransformPlugin,
+    window::WindowPlugin,
+};
+use bevy_asset_loader::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_egui::EguiPlugin;
+use bevy_rapier2d::prelude::*;
+use checkpoint::{CheckpointBundle, CheckpointPlugin};
+use components::wall_collisions::*;
+#[cfg(feature = "inspector")]
+use debug::*;
+#[cfg(feature = "dev-tools")]
+use dev_tools::DevToolsPlugin;
+use game_state::{GameState, PausePlugin};
+use graphics::animation::AnimationPlugin;
+use graphics::facing::FacingPlugin;
+use graphics::y_sort::YSortPlugin;
+use graphics::GraphicsPlugin;
+use hot_reload::HotReloadPlugin;
+use input::ActionsPlugin;
+use iyes_loopless::prelude::*;
+use level_exit::{LevelExitBundle, LevelExitPlugin};
+use loading::LoadingPlugin;
+use menu::MenuPlugin;
+use mutant::{MutantBundle, MutantPlugin};
+use player::*;
+use replay::ReplayPlugin;
+use resources::*;
+use rock::{RockBundle, RockPlugin};
+use save::SavePlugin;
+use spatial_index::SpatialIndexPlugin;
+use std::time::{Duration, Instant};
+use timestep::{fixed_timestep, TimestepPlugin};
+use utils::asset_loader_ldtk::LdtkTextureAssetPlugin;
+
+pub mod checkpoint;
+pub mod components;
+#[cfg(feature = "inspector")]
+pub mod debug;
+#[cfg(feature = "dev-tools")]
+pub mod dev_tools;
+pub mod game_state;
+pub mod graphics;
+pub mod hot_reload;
+pub mod input;
+pub mod level_exit;
+pub mod loading;
+pub mod menu;
+pub mod mutant;
+pub mod player;
+pub mod replay;
+pub mod resources;
+pub mod rock;
+pub mod save;
+pub mod spatial_index;
+pub mod timestep;
+pub mod utils;
+
+pub const LEVEL_ONE_ID: &str = "01a63d70-5110-11ed-a5d6-d713966358e6";
+
+/// The game in a window, `main` runs this.
+pub fn app(plugins: GraveyardShiftPlugins) -> App {
+    let audio = plugins.audio;
+
+    let mut app = App::new();
+    app.insert_resource(ImageSettings::default_nearest())
+        .insert_resource(WindowDescriptor {
+            width: 1280.0,
+            height: 720.0,
+            title: "Graveyard Shift".into(),
+            present_mode: bevy::window::PresentMode::AutoVsync,
+            resizable: true,
+            ..Default::default()
+        })
+        .insert_resource(AssetServerSettings {
+            // Lets the design team edit Map.ldtk while the game is running
+            watch_for_changes: cfg!(feature = "dev"),
+            ..Default::default()
+        })
+        .add_plugins_with(DefaultPlugins, |group| {
+            if !audio {
+                group.disable::<AudioPlugin>();
+            }
+            group
+        })
+        .add_plugins(plugins);
+    app
+}
+
+/// Every plugin of the game, on top of bevy's `DefaultPlugins`.
+///
+/// The flags turn optional parts on and off, `Default` turns on everything
+/// that was compiled in.
+pub struct GraveyardShiftPlugins {
+    /// The world and entity inspector windows, needs the `inspector` feature.
+    pub inspector: bool,
+    /// The debug overlay, console and telemetry panel, needs the `dev-tools` feature.
+    pub dev_tools: bool,
+    /// Draws the rapier colliders over the game, needs the `physics-debug` feature.
+    pub physics_debug: bool,
+    /// Bevy's audio output, the game has no sounds of its own yet.
+    pub audio: bool,
+}
+
+impl Default for GraveyardShiftPlugins {
+    fn default() -> Self {
+        Self {
+            inspector: cfg!(feature = "inspector"),
+            dev_tools: cfg!(feature = "dev-tools"),
+            physics_debug: cfg!(feature = "physics-debug"),
+            audio: true,
+        }
+    }
+}
+
+impl PluginGroup for GraveyardShiftPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(GameplayPlugin)
+            .add(EguiPlugin)
+            .add(GraphicsPlugin)
+            .add(MenuPlugin)
+            .add(SavePlugin)
+            .add(HotReloadPlugin);
+
+        #[cfg(feature = "inspector")]
+        if self.inspector {
+            group.add(DebugPlugin);
+        }
+        #[cfg(feature = "dev-tools")]
+        if self.dev_tools {
+            group.add(DevToolsPlugin);
+        }
+        #[cfg(feature = "physics-debug")]
+        if self.physics_debug {
+            group.add(RapierDebugRenderPlugin::default());
+        }
+    }
+}
+
+/// The game without a window or renderer, for simulating it in tests.
+///
+/// Loads `Map.ldtk` like the real game and goes straight to
+/// `GameState::Playing` once it's spawned, there is no main menu.
+/// `Time` moves forward by exactly `HeadlessFrameTime` on every
+/// `App::update`, however long the update took, so runs are repeatable.
+/// That's one `fixed_timestep` unless changed, so every update runs one
+/// gameplay and physics tick.
+///
+/// Input is simulated by sending `KeyboardInput` events,
+/// they go through the same bindings and `Actions` as real input.
+/// The dev tools aren't added, add `DevToolsPlugin` before the first
+/// update to run console commands.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.insert_resource(ImageSettings::default_nearest())
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_plugin(WindowPlugin {
+            add_primary_window: false,
+            exit_on_all_closed: false,
+            close_when_requested: false,
+        })
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(ImagePlugin)
+        // Usually added by the render plugins, gameplay only needs to store them
+        .add_asset::<TextureAtlas>()
+        .add_asset::<Mesh>()
+        .add_asset::<ColorMaterial>()
+        .insert_resource(HeadlessFrameTime(fixed_timestep()))
+        .add_system_to_stage(CoreStage::First, step_headless_time.after(TimeSystem))
+        .add_plugin(GameplayPlugin)
+        .add_enter_system(GameState::MainMenu, skip_main_menu);
+    app
+}
+
+/// How long every update of a `headless_app` takes in game time.
+///
+/// Can be changed between updates to simulate other frame rates.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct HeadlessFrameTime(pub Duration);
+
+/// Replaces the real clock with one that ticks `HeadlessFrameTime` per update.
+fn step_headless_time(
+    mut time: ResMut<Time>,
+    frame_time: Res<HeadlessFrameTime>,
+    mut clock: Local<Option<(Time, Instant)>>,
+) {
+    let (headless_time, now) = clock.get_or_insert_with(|| {
+        let headless_time = Time::default();
+        let startup = headless_time.startup();
+        (headless_time, startup)
+    });
+
+    *now += frame_time.0;
+    headless_time.update_with_instant(*now);
+    *time = headless_time.clone();
+}
+
+fn skip_main_menu(mut commands: Commands) {
+    commands.insert_resource(NextState(GameState::Playing));
+}
+
+/// Loading the world, physics and everything the player can do.
+///
+/// Nothing in here needs a window, so it's shared by `app` and `headless_app`.
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_loopless_state(GameState::AssetLoading)
+            .add_loading_state(
+                LoadingState::new(GameState::AssetLoading)
+                    .with_collection::<WorldAssets>()
+                    .with_collection::<TextureAssets>(),
+            )
+            .insert_resource(LevelSelection::Iid(LEVEL_ONE_ID.into()))
+            .init_resource::<CurrentLevel>()
+            .init_resource::<CompletedLevels>()
+            .init_resource::<Cheats>()
+            .add_system(track_current_level)
+            .init_resource::<WallMergeStats>()
+            .add_system(track_wall_merge_stats.before(spawn_wall_collision))
+            .add_system(spawn_wall_collision)
+            .add_plugin(LdtkPlugin)
+            .insert_resource(LdtkSettings {
+                int_grid_rendering: IntGridRendering::Invisible,
+                ..Default::default()
+            })
+            .add_plugin(
+                RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0)
+                    .with_default_system_setup(false),
+            )
+            .insert_resource(RapierConfiguration {
+                gravity: Vec2::ZERO,
+                // Stepped once per tick of the `TimestepPlugin`
+                timestep_mode: TimestepMode::Fixed {
+                    dt: fixed_timestep().as_secs_f32(),
+                    substeps: 1,
+                },
+                ..Default::default()
+            })
+            .add_plugin(TimestepPlugin)
+            .add_plugin(SpatialIndexPlugin)
+            .add_plugin(LdtkTextureAssetPlugin)
+            .add_plugin(LoadingPlugin)
+            .add_plugin(ActionsPlugin)
+            .add_plugin(PausePlugin)
+            .add_plugin(PlayerPlugin)
+            .add_plugin(MutantPlugin)
+            .add_plugin(RockPlugin)
+            .add_plugin(CheckpointPlugin)
+            .add_plugin(LevelExitPlugin)
+            .add_plugin(ReplayPlugin)
+            .add_plugin(AnimationPlugin)
+            .add_plugin(FacingPlugin)
+            .add_plugin(YSortPlugin)
+            .register_ldtk_int_cell::<WallBundle>(1)
+            .register_ldtk_entity::<PlayerBundle>("Player")
+            .register_ldtk_entity::<MutantBundle>("Mutant")
+            .register_ldtk_entity::<RockBundle>("Rock")
+            .register_ldtk_entity::<CheckpointBundle>("Checkpoint")
+            .register_ldtk_entity::<LevelExitBundle>("Exit");
+    }
+}
@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use iyes_loopless::prelude::*;
+use iyes_progress::prelude::*;
+
+use crate::{
+    components::wall_collisions::WallMergeStats,
+    game_state::GameState,
+    resources::{TextureAssets, WorldAssets},
+};
+
+/// Shows a progress bar while the assets load and the first level spawns.
+///
+/// `GameState::AssetLoading` only moves on to the main menu once every
+/// tracked step is done, including the wall colliders of the level,
+/// so the player can't fall through walls that haven't been spawned yet.
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(
+            ProgressPlugin::new(GameState::AssetLoading).continue_to(GameState::MainMenu),
+        )
+        .add_enter_system(GameState::AssetLoading, spawn_loading_screen)
+        .add_system(
+            spawn_world
+                .track_progress()
+                .run_in_state(GameState::AssetLoading),
+        )
+        .add_system(
+            track_level_spawning
+                .track_progress()
+                .run_in_state(GameState::AssetLoading),
+        )
+        .add_system(update_loading_bar.run_in_state(GameState::AssetLoading))
+        .add_exit_system(GameState::AssetLoading, despawn_loading_screen);
+    }
+}
+
+const LOADING_BAR_WIDTH: f32 = 320.;
+const LOADING_BAR_HEIGHT: f32 = 12.;
+
+/// Everything that belongs to the loading screen and goes away with it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct LoadingScreen;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct LoadingBar;
+
+fn spawn_loading_screen(mut commands: Commands) {
+    // The pixel camera only spawns once playing, the UI needs a camera of its own
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(LoadingScreen)
+        .insert(Name::new("Loading Camera"));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            // Hides the level while it spawns behind the loading screen
+            color: Color::BLACK.into(),
+            ..Default::default()
+        })
+        .insert(LoadingScreen)
+        .insert(Name::new("Loading Screen"))
+        .with_children(|screen| {
+            screen
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(LOADING_BAR_WIDTH), Val::Px(LOADING_BAR_HEIGHT)),
+                        ..Default::default()
+                    },
+                    color: Color::rgba(1., 1., 1., 0.15).into(),
+                    ..Default::default()
+                })
+                .with_children(|bar| {
+                    bar.spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                            ..Default::default()
+                        },
+                        color: Color::WHITE.into(),
+                        ..Default::default()
+                    })
+                    .insert(LoadingBar);
+                });
+        });
+}
+
+fn update_loading_bar(
+    progress: Option<Res<ProgressCounter>>,
+    mut bar_query: Query<&mut Style, With<LoadingBar>>,
+) {
+    if let Some(progress) = progress {
+        let Progress { done, total } = progress.progress();
+        let fraction = if total > 0 {
+            done as f32 / total as f32
+        } else {
+            0.
+        };
+
+        for mut style in bar_query.iter_mut() {
+            style.size.width = Val::Percent(fraction * 100.);
+        }
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, query: Query<Entity, With<LoadingScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Spawns the LDtk world as soon as `Map.ldtk` and the textures are loaded.
+///
+/// `apply_ldtk_texture_assets` only gives entities their atlas on the frame
+/// they spawn, so entities spawned before `TextureAssets` would keep none.
+fn spawn_world(
+    mut commands: Commands,
+    assets: Option<Res<WorldAssets>>,
+    textures: Option<Res<TextureAssets>>,
+    mut spawned: Local<bool>,
+) -> Progress {
+    if !*spawned {
+        if let (Some(assets), Some(_)) = (assets, textures) {
+            commands.spawn_bundle(LdtkWorldBundle {
+                ldtk_handle: assets.map.clone(),
+                ..Default::default()
+            });
+            *spawned = true;
+        }
+    }
+
+    (*spawned).into()
+}
+
+/// Waits for the selected level to spawn and for its walls to get colliders.
+///
+/// Levels without walls count as ready once they've spawned,
+/// see `track_wall_merge_stats`.
+fn track_level_spawning(
+    mut level_events: EventReader<LevelEvent>,
+    merge_stats: Res<WallMergeStats>,
+    mut spawned_level: Local<Option<String>>,
+) -> Progress {
+    for level_event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = level_event {
+            *spawned_level = Some(iid.clone());
+        }
+    }
+
+    let walls_ready = spawned_level
+        .as_ref()
+        .map_or(false, |iid| merge_stats.0.contains_key(iid));
+
+    Progress {
+        done: u32::from(spawned_level.is_some()) + u32::from(walls_ready),
+        total: 2,
+    }
+}
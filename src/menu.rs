@@ -0,0 +1,80 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use iyes_loopless::prelude::*;
+
+use crate::{
+    game_state::{GameState, PauseState},
+    save::{apply_save, LoadedSave},
+};
+
+/// The title screen shown once loading is done.
+///
+/// The first level is already spawned behind it, so a new game starts
+/// right away and continuing only respawns if the save is in another level.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(GameState::MainMenu, spawn_menu_screen)
+            .add_system(main_menu.run_in_state(GameState::MainMenu))
+            .add_exit_system(GameState::MainMenu, despawn_menu_screen);
+    }
+}
+
+/// Everything that belongs to the main menu and goes away with it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct MenuScreen;
+
+fn spawn_menu_screen(mut commands: Commands) {
+    // Keeps the player from walking around behind the menu
+    commands.insert_resource(NextState(PauseState::Paused));
+
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(MenuScreen)
+        .insert(Name::new("Menu Camera"));
+}
+
+fn despawn_menu_screen(mut commands: Commands, query: Query<Entity, With<MenuScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn main_menu(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    loaded_save: Res<LoadedSave>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    egui::CentralPanel::default().show(egui_context.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.);
+            ui.heading("Graveyard Shift");
+            ui.add_space(24.);
+
+            let continue_button =
+                ui.add_enabled(loaded_save.0.is_some(), egui::Button::new("Continue"));
+            if continue_button.clicked() {
+                if let Some(save) = &loaded_save.0 {
+                    apply_save(&mut commands, save);
+                    start_playing(&mut commands);
+                }
+            }
+
+            if ui.button("New Game").clicked() {
+                start_playing(&mut commands);
+            }
+
+            if ui.button("Quit").clicked() {
+                exit_events.send(AppExit);
+            }
+        });
+    });
+}
+
+fn start_playing(commands: &mut Commands) {
+    commands.insert_resource(NextState(GameState::Playing));
+    commands.insert_resource(NextState(PauseState::Running));
+}
@@ -1,24 +1,208 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
+#[cfg(feature = "inspector")]
 use bevy_inspector_egui::prelude::*;
 use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
 
-use crate::{components::physics::*, graphics::camera::CameraAnchor};
+use crate::{
+    checkpoint::Dying,
+    components::physics::*,
+    graphics::{
+        animation::{Animation, AnimationState, Clip},
+        camera::CameraAnchor,
+        camera_shake::CameraShakeEvent,
+        facing::Facing,
+        y_sort::YSort,
+    },
+    player::{Player, SoundPoint},
+    resources::{Cheats, TextureAssets},
+    spatial_index::{SpatialIndex, SpatialIndexed},
+    timestep::{FixedTimestepEventAppExt, Interpolated, FIXED_UPDATE, GAMEPLAY_SUBSTAGE},
+    utils::asset_loader_ldtk::LdtkTextureAssetLoader,
+};
 
-#[derive(Component, Default, Inspectable)]
+pub struct MutantPlugin;
+
+impl Plugin for MutantPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep_event::<PlayerCaughtEvent>()
+            .init_resource::<HearingChecks>()
+            .add_fixed_timestep_system(FIXED_UPDATE, GAMEPLAY_SUBSTAGE, mutant_catch_player)
+            .add_fixed_timestep_system(FIXED_UPDATE, GAMEPLAY_SUBSTAGE, mutants_hear_sounds);
+    }
+}
+
+#[derive(Component, Default)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
 pub struct Mutant;
 
+impl LdtkTextureAssetLoader for Mutant {
+    fn get_asset(&self, assets: &Res<TextureAssets>) -> Handle<TextureAtlas> {
+        assets.mutant.clone()
+    }
+}
+
+/// How far away a mutant hears sound dots.
+const HEARING_RADIUS: f32 = 64.;
+
+/// Mutants hear the sound dots that come close to them.
+///
+/// Nothing reacts to it yet, the overlay draws it.
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct Hearing {
+    pub radius: f32,
+    /// The closest sound dot heard on the last tick, in world space.
+    pub heard: Option<Vec2>,
+}
+
+impl Default for Hearing {
+    fn default() -> Self {
+        Self {
+            radius: HEARING_RADIUS,
+            heard: None,
+        }
+    }
+}
+
+/// How many entities `mutants_hear_sounds` looked at on the last tick.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct HearingChecks {
+    /// Entities the `SpatialIndex` found near the mutants.
+    pub candidates: usize,
+    /// Every sound dot for every mutant, what it would take without the index.
+    pub without_index: usize,
+}
+
+/// Sent when a mutant touches the player.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PlayerCaughtEvent {
+    pub mutant: Entity,
+    pub player: Entity,
+}
+
+/// A player who was already caught can't be caught again until they respawn.
+pub fn mutant_catch_player(
+    rapier_context: Res<RapierContext>,
+    cheats: Res<Cheats>,
+    mutant_query: Query<Entity, With<Mutant>>,
+    player_query: Query<Entity, (With<Player>, Without<Dying>)>,
+    mut caught_events: EventWriter<PlayerCaughtEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+) {
+    if cheats.god {
+        return;
+    }
+
+    let player = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for mutant in mutant_query.iter() {
+        let touching = rapier_context
+            .contact_pair(mutant, player)
+            .map_or(false, |contact| contact.has_any_active_contacts());
+
+        if touching {
+            caught_events.send(PlayerCaughtEvent { mutant, player });
+            shake_events.send(CameraShakeEvent::PLAYER_CAUGHT);
+        }
+    }
+}
+
+pub fn mutants_hear_sounds(
+    index: Res<SpatialIndex>,
+    mut checks: ResMut<HearingChecks>,
+    mut mutant_query: Query<(Entity, &mut Hearing)>,
+    sound_query: Query<(), With<SoundPoint>>,
+) {
+    let mut candidates = 0;
+    let mut mutants = 0;
+
+    for (mutant, mut hearing) in mutant_query.iter_mut() {
+        mutants += 1;
+        let position = match index.position(mutant) {
+            Some(position) => position,
+            None => continue,
+        };
+
+        let heard = index
+            .within(position, hearing.radius)
+            .inspect(|_| candidates += 1)
+            .filter(|&entity| sound_query.contains(entity))
+            .filter_map(|dot| index.position(dot))
+            .filter(|dot| dot.distance(position) <= hearing.radius)
+            .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+
+        if hearing.heard != heard {
+            hearing.heard = heard;
+        }
+    }
+
+    *checks = HearingChecks {
+        candidates,
+        without_index: mutants * sound_query.iter().count(),
+    };
+}
+
 #[derive(Bundle, Default, LdtkEntity)]
 pub struct MutantBundle {
     #[with(mutant_collider)]
     #[bundle]
     collider: ColliderBundle,
     mutant: Mutant,
-    #[sprite_sheet_bundle("mutant.png", 32.0, 32.0, 4, 1, 0.0, 0.0, 0)]
     #[bundle]
     sprite_bundle: SpriteSheetBundle,
     #[with(mutant_camera_anchor)]
     camera_anchor: CameraAnchor,
+    #[with(mutant_animation)]
+    animation: Animation,
+    #[with(art_faces_left)]
+    facing: Facing,
+    y_sort: YSort,
+    interpolated: Interpolated,
+    spatial_indexed: SpatialIndexed,
+    hearing: Hearing,
+}
+
+impl MutantBundle {
+    /// A mutant that wasn't placed in LDtk, at `translation` in the space of `parent`.
+    pub fn spawn(commands: &mut Commands, parent: Entity, translation: Vec3) -> Entity {
+        let entity_instance = EntityInstance::default();
+        let mutant = commands
+            .spawn_bundle(MutantBundle {
+                collider: mutant_collider(entity_instance.clone()),
+                sprite_bundle: SpriteSheetBundle {
+                    transform: Transform::from_translation(translation),
+                    ..Default::default()
+                },
+                camera_anchor: mutant_camera_anchor(entity_instance.clone()),
+                animation: mutant_animation(entity_instance.clone()),
+                facing: art_faces_left(entity_instance),
+                ..Default::default()
+            })
+            .insert(Name::new("Mutant"))
+            .id();
+
+        commands.entity(parent).add_child(mutant);
+        mutant
+    }
+}
+
+fn mutant_animation(_: EntityInstance) -> Animation {
+    let walk = Clip::looping(0, 3, 0.15);
+    Animation::new([
+        (AnimationState::Idle, Clip::looping(0, 0, 1.)),
+        (AnimationState::WalkUp, walk),
+        (AnimationState::WalkDown, walk),
+        (AnimationState::WalkLeft, walk),
+        (AnimationState::WalkRight, walk),
+    ])
+}
+
+fn art_faces_left(_: EntityInstance) -> Facing {
+    Facing::art_faces_left()
 }
 
 fn mutant_camera_anchor(_: EntityInstance) -> CameraAnchor {
@@ -1,86 +1,299 @@
 use std::f32::consts::PI;
 
+use crate::checkpoint::Dying;
 use crate::components::physics::*;
 use crate::components::wall_collisions::WallCollider;
+use crate::game_state::PauseState;
+use crate::graphics::animation::{Animation, AnimationState, Clip};
 use crate::graphics::camera::CameraAnchor;
+use crate::graphics::camera_shake::CameraShakeEvent;
+use crate::graphics::facing::{face_movement_direction, Facing};
+use crate::graphics::y_sort::YSort;
+use crate::input::{Action, TickActions};
+use crate::mutant::{mutant_catch_player, PlayerCaughtEvent};
+use crate::resources::{Cheats, CurrentLevel, TextureAssets};
+use crate::spatial_index::SpatialIndexed;
+use crate::timestep::{
+    fixed_timestep, FixedTimestepEventAppExt, Interpolated, FIXED_UPDATE, GAMEPLAY_SUBSTAGE,
+};
+use crate::utils::asset_loader_ldtk::LdtkTextureAssetLoader;
 use bevy::prelude::shape::Circle;
 use bevy::prelude::*;
 use bevy::sprite::Mesh2dHandle;
 use bevy_ecs_ldtk::prelude::*;
+#[cfg(feature = "inspector")]
 use bevy_inspector_egui::Inspectable;
 use bevy_rapier2d::na::ComplexField;
 use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(player_movement)
-            .add_system(player_sound)
-            .add_system(kill_old_sounds)
-            .add_system(wall_sound_collisions);
+        app.add_fixed_timestep_event::<SoundWaveEvent>()
+            .add_fixed_timestep_system_set(
+                FIXED_UPDATE,
+                GAMEPLAY_SUBSTAGE,
+                ConditionSet::new()
+                    .run_in_state(PauseState::Running)
+                    .with_system(player_movement)
+                    .with_system(player_noclip)
+                    .with_system(recharge_noise_budget)
+                    .with_system(
+                        player_sound
+                            .after(recharge_noise_budget)
+                            .after(face_movement_direction),
+                    )
+                    .with_system(spawn_sound_waves.after(player_sound))
+                    .with_system(player_death_animation.after(mutant_catch_player))
+                    .with_system(kill_old_sounds)
+                    .with_system(wall_sound_collisions)
+                    .into(),
+            )
+            .add_system(restore_player_position);
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component, Inspectable)]
+/// How much of the movement force is used while sneaking.
+const SNEAK_FORCE_FACTOR: f32 = 0.4;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
 pub struct Player;
 
-#[derive(Component, Default, Clone, Inspectable)]
+impl LdtkTextureAssetLoader for Player {
+    fn get_asset(&self, assets: &Res<TextureAssets>) -> Handle<TextureAtlas> {
+        assets.player.clone()
+    }
+}
+
+#[derive(Component, Default, Clone)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
 pub struct MovementForce(pub f32);
 
-fn player_movement(
-    mut player_query: Query<(&MovementForce, &mut ExternalForce), With<Player>>,
-    keyboard: Res<Input<KeyCode>>,
-) {
-    if let Ok((MovementForce(mvt_force), mut ext_force)) = player_query.get_single_mut() {
-        let mut move_dir = Vec2::default();
+/// Moves the player to `translation` once `level_iid` is spawned.
+///
+/// For putting the player back after the world respawns or a game is loaded.
+/// The translation is relative to the level, like the player's `Transform`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PendingPlayerPosition {
+    pub level_iid: String,
+    pub translation: Vec3,
+}
 
-        if keyboard.pressed(KeyCode::W) {
-            move_dir.y += 1.;
+/// The depth comes from the new LDtk data in case the layers were reordered.
+fn restore_player_position(
+    mut commands: Commands,
+    pending_position: Option<Res<PendingPlayerPosition>>,
+    current_level: Res<CurrentLevel>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    if let Some(pending_position) = pending_position {
+        if current_level.0.as_ref() != Some(&pending_position.level_iid) {
+            return;
         }
-        if keyboard.pressed(KeyCode::S) {
-            move_dir.y -= 1.;
+
+        if let Ok(mut player_transform) = player_query.get_single_mut() {
+            player_transform.translation = pending_position
+                .translation
+                .truncate()
+                .extend(player_transform.translation.z);
+            commands.remove_resource::<PendingPlayerPosition>();
         }
-        if keyboard.pressed(KeyCode::D) {
-            move_dir.x += 1.;
+    }
+}
+
+/// Limits how much noise the player can make.
+///
+/// Every sound wave costs some of the budget, which slowly recharges,
+/// and there is a short cooldown between two waves.
+/// Keeps echolocation a resource to manage and stops the player from
+/// flooding the world with sound dots.
+#[derive(Component, Clone, Debug)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
+pub struct NoiseBudget {
+    pub current: f32,
+    pub max: f32,
+    /// Budget regained every second.
+    pub recharge_rate: f32,
+    #[cfg_attr(feature = "inspector", inspectable(ignore))]
+    pub cooldown: Timer,
+}
+
+impl Default for NoiseBudget {
+    fn default() -> Self {
+        let mut cooldown = Timer::from_seconds(0.4, false);
+        // Ready for the first sound straight away
+        cooldown.tick(cooldown.duration());
+
+        Self {
+            current: 3.,
+            max: 3.,
+            recharge_rate: 0.5,
+            cooldown,
         }
-        if keyboard.pressed(KeyCode::A) {
-            move_dir.x -= 1.;
+    }
+}
+
+impl NoiseBudget {
+    /// Spends `cost` if there is enough budget and the cooldown is over.
+    pub fn try_spend(&mut self, cost: f32) -> bool {
+        if !self.cooldown.finished() || self.current < cost {
+            return false;
         }
 
-        // If move_dir is not a vector of length 0.0.
-        if move_dir.try_normalize().is_some() {
-            ext_force.force = move_dir * (*mvt_force);
+        self.current -= cost;
+        self.cooldown.reset();
+        true
+    }
+
+    /// How full the budget is, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0. {
+            (self.current / self.max).clamp(0., 1.)
         } else {
-            ext_force.force = Vec2::splat(0.);
+            0.
         }
     }
 }
 
-#[derive(Component)]
-struct SoundPoint;
+fn recharge_noise_budget(mut budget_query: Query<&mut NoiseBudget>) {
+    let delta = fixed_timestep();
+    for mut budget in budget_query.iter_mut() {
+        budget.cooldown.tick(delta);
+        budget.current =
+            (budget.current + budget.recharge_rate * delta.as_secs_f32()).min(budget.max);
+    }
+}
+
+fn player_movement(
+    mut player_query: Query<(&MovementForce, &mut ExternalForce, Option<&Dying>), With<Player>>,
+    actions: Res<TickActions>,
+) {
+    if let Ok((MovementForce(mvt_force), mut ext_force, dying)) = player_query.get_single_mut() {
+        if dying.is_some() {
+            ext_force.force = Vec2::ZERO;
+            return;
+        }
+
+        let mut force = *mvt_force;
+        if actions.pressed(Action::Sneak) {
+            force *= SNEAK_FORCE_FACTOR;
+        }
+
+        // Keyboard movement is always full strength,
+        // gamepad sticks can ask for anything in between.
+        ext_force.force = actions.movement * force;
+    }
+}
+
+/// Stops the player from colliding with walls while `Cheats::noclip` is on.
+fn player_noclip(cheats: Res<Cheats>, mut player_query: Query<&mut CollisionGroups, With<Player>>) {
+    let filters = if cheats.noclip {
+        Group::all() - WALL_PHYS_LAYER
+    } else {
+        Group::all()
+    };
+
+    for mut collision_groups in player_query.iter_mut() {
+        if collision_groups.filters != filters {
+            collision_groups.filters = filters;
+        }
+    }
+}
+
+/// One of the dots a `SoundWave` is made of.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct SoundPoint;
 
 #[derive(Component)]
 struct SoundLifetime(Timer);
 
-fn kill_old_sounds(
-    mut commands: Commands,
-    mut query: Query<(Entity, &mut SoundLifetime)>,
-    time: Res<Time>,
-) {
+fn kill_old_sounds(mut commands: Commands, mut query: Query<(Entity, &mut SoundLifetime)>) {
     for (entity, mut sound_lifetime) in query.iter_mut() {
         // timers gotta be ticked, to work
-        sound_lifetime.0.tick(time.delta());
+        sound_lifetime.0.tick(fixed_timestep());
 
         // if it finished, despawn the bomb
         if sound_lifetime.0.finished() {
-            commands.entity(entity).despawn();
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// The shape of a burst of sound dots.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SoundWave {
+    pub number_of_dots: u8,
+    /// Angle in radians the wave is centered on.
+    pub direction: f32,
+    /// Angle in radians the dots are spread over, a full ring is `2 * PI`.
+    pub spread: f32,
+    pub speed: f32,
+    /// Seconds before the dots fade out.
+    pub lifetime: f32,
+    /// How much of the player's `NoiseBudget` it uses up.
+    pub cost: f32,
+}
+
+impl SoundWave {
+    /// The player clapping, a full ring that lights up the surroundings.
+    pub const CLAP: Self = Self {
+        number_of_dots: 64,
+        direction: 0.,
+        spread: 2. * PI,
+        speed: 50.,
+        lifetime: 2.,
+        cost: 1.,
+    };
+
+    /// Something hitting a wall, a smaller ring than a clap.
+    pub const IMPACT: Self = Self {
+        number_of_dots: 32,
+        direction: 0.,
+        spread: 2. * PI,
+        speed: 45.,
+        lifetime: 1.5,
+        cost: 0.,
+    };
+
+    /// A narrow, fast echo that reaches further down a corridor.
+    pub fn aimed(direction: Vec2) -> Self {
+        Self {
+            number_of_dots: 24,
+            direction: direction.y.atan2(direction.x),
+            spread: PI / 5.,
+            speed: 80.,
+            lifetime: 3.,
+            cost: 0.5,
         }
     }
+
+    fn velocities(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let full_ring = self.spread >= 2. * PI;
+        // A ring would put the first and last dot on top of each other,
+        // a cone needs dots on both of its edges.
+        let steps = if full_ring || self.number_of_dots < 2 {
+            f32::from(self.number_of_dots)
+        } else {
+            f32::from(self.number_of_dots - 1)
+        };
+        let start = if full_ring {
+            self.direction
+        } else {
+            self.direction - self.spread / 2.
+        };
+
+        (0..self.number_of_dots).map(move |i| {
+            let angle = start + f32::from(i) * self.spread / steps;
+            Vec2::new(angle.cos(), angle.sin()) * self.speed
+        })
+    }
 }
 
 fn make_sound_dots(
-    number_of_dots: i8,
+    wave: SoundWave,
     commands: &mut Commands,
     transform: &Transform,
     mesh: &Mesh2dHandle,
@@ -112,38 +325,87 @@ fn make_sound_dots(
                 combine_rule: CoefficientCombineRule::Max,
             })
             .insert(ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC)
+            // Not `Interpolated`, a `Visuals` child per dot would double the
+            // entities of the most numerous thing in the game
+            .insert(SpatialIndexed)
             .insert(Name::new("Sound Dot"))
-            .insert(SoundLifetime(Timer::from_seconds(2., false)));
+            .insert(SoundLifetime(Timer::from_seconds(wave.lifetime, false)));
     };
 
-    for i in 0i8..number_of_dots {
-        let angle = f32::from(i) * 2. * PI / number_of_dots as f32;
-        make_sound_dot(Vec2::new(angle.cos(), angle.sin()) * 50.);
+    for velocity in wave.velocities() {
+        make_sound_dot(velocity);
     }
 }
 
-fn player_sound(
+/// Request to spawn the dots of a `SoundWave` at a point in the world.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SoundWaveEvent {
+    pub origin: Vec3,
+    pub wave: SoundWave,
+}
+
+pub fn spawn_sound_waves(
     mut commands: Commands,
-    player_query: Query<&Transform, With<Player>>,
-    keyboard: Res<Input<KeyCode>>,
+    mut wave_events: EventReader<SoundWaveEvent>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    if keyboard.just_pressed(KeyCode::Space) {
-        if let Ok(transform) = player_query.get_single() {
-            let circle = Circle::new(1.0);
-            let handle = meshes.add(Mesh::from(circle));
-            let material = ColorMaterial::from(Color::WHITE);
-            let material_handle = materials.add(material);
-
-            make_sound_dots(
-                64,
-                &mut commands,
-                transform,
-                &handle.into(),
-                &material_handle,
-            );
+    for event in wave_events.iter() {
+        let circle = Circle::new(1.0);
+        let handle = meshes.add(Mesh::from(circle));
+        let material = ColorMaterial::from(Color::WHITE);
+        let material_handle = materials.add(material);
+
+        make_sound_dots(
+            event.wave,
+            &mut commands,
+            &Transform::from_translation(event.origin),
+            &handle.into(),
+            &material_handle,
+        );
+    }
+}
+
+fn player_sound(
+    mut player_query: Query<
+        (&GlobalTransform, &Facing, &mut NoiseBudget, &mut Animation),
+        With<Player>,
+    >,
+    actions: Res<TickActions>,
+    mut wave_events: EventWriter<SoundWaveEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+) {
+    let aimed = actions.just_pressed(Action::AimedSound);
+    if !aimed && !actions.just_pressed(Action::MakeSound) {
+        return;
+    }
+
+    if let Ok((global_transform, facing, mut noise_budget, mut animation)) =
+        player_query.get_single_mut()
+    {
+        let origin = global_transform.translation();
+        let wave = if aimed {
+            // Without a cursor, like on a gamepad, aim the way the player looks
+            let direction = actions
+                .cursor_world_position
+                .map_or(Some(facing.direction()), |cursor| {
+                    (cursor - origin.truncate()).try_normalize()
+                });
+            match direction {
+                Some(direction) => SoundWave::aimed(direction),
+                None => return,
+            }
+        } else {
+            SoundWave::CLAP
+        };
+
+        if !noise_budget.try_spend(wave.cost) {
+            return;
         }
+
+        wave_events.send(SoundWaveEvent { origin, wave });
+        animation.play_once(AnimationState::EmitSound);
+        shake_events.send(CameraShakeEvent::LOUD_SOUND);
     }
 }
 
@@ -155,20 +417,29 @@ fn wall_sound_collisions(
     for (entity, mut sound_velocity) in sound_query.iter_mut() {
         for contact in rapier_context.contacts_with(entity) {
             if contact.has_any_active_contacts() {
-                let other_collider = if contact.collider1() == entity {
+                let sound_is_first = contact.collider1() == entity;
+                let other_collider = if sound_is_first {
                     contact.collider2()
                 } else {
                     contact.collider1()
                 };
 
                 if wall_query.contains(other_collider) {
-                    if let Some(manifold) = contact.manifold(0) {
-                        let normal = manifold.normal();
-                        if normal.x.abs() > 0. {
-                            sound_velocity.linvel.x *= -1.;
+                    for manifold in contact.manifolds() {
+                        if manifold.num_points() == 0 {
+                            continue;
                         }
-                        if normal.y.abs() > 0. {
-                            sound_velocity.linvel.y *= -1.;
+
+                        // The manifold normal points from collider1 to collider2,
+                        // flip it so it always points out of the wall
+                        let normal = if sound_is_first {
+                            -manifold.normal()
+                        } else {
+                            manifold.normal()
+                        };
+
+                        if let Some(velocity) = reflect_sound(sound_velocity.linvel, normal) {
+                            sound_velocity.linvel = velocity;
                         }
                     }
                 }
@@ -177,19 +448,70 @@ fn wall_sound_collisions(
     }
 }
 
+/// Bounces a sound dot moving with `velocity` off a wall facing `normal`.
+///
+/// Returns `None` when the dot is already moving away from the wall,
+/// so a dot touching a wall for several frames only bounces once and a dot
+/// sliding along a wall doesn't get flipped on the wrong axis.
+fn reflect_sound(velocity: Vec2, normal: Vec2) -> Option<Vec2> {
+    let normal = normal.try_normalize()?;
+    let approach = velocity.dot(normal);
+
+    if approach < 0. {
+        Some(velocity - 2. * approach * normal)
+    } else {
+        None
+    }
+}
+
+fn player_death_animation(
+    mut caught_events: EventReader<PlayerCaughtEvent>,
+    mut animation_query: Query<&mut Animation, With<Player>>,
+) {
+    for event in caught_events.iter() {
+        if let Ok(mut animation) = animation_query.get_mut(event.player) {
+            animation.play_once(AnimationState::Death);
+        }
+    }
+}
+
 #[derive(Bundle, Default, LdtkEntity)]
 pub struct PlayerBundle {
     player: Player,
     #[with(player_movement_force)]
     mvt_force: MovementForce,
+    noise_budget: NoiseBudget,
     #[with(player_collider)]
     #[bundle]
     collider: ColliderBundle,
     #[with(player_camera_anchor)]
     camera_anchor: CameraAnchor,
-    #[sprite_sheet_bundle("player.png", 32.0, 32.0, 8, 1, 0.0, 0.0, 0)]
     #[bundle]
     sprite_bundle: SpriteSheetBundle,
+    #[with(player_animation)]
+    animation: Animation,
+    #[with(art_faces_left)]
+    facing: Facing,
+    y_sort: YSort,
+    interpolated: Interpolated,
+    spatial_indexed: SpatialIndexed,
+}
+
+fn player_animation(_: EntityInstance) -> Animation {
+    let walk = Clip::looping(0, 7, 0.1);
+    Animation::new([
+        (AnimationState::Idle, Clip::looping(0, 0, 1.)),
+        (AnimationState::WalkUp, walk),
+        (AnimationState::WalkDown, walk),
+        (AnimationState::WalkLeft, walk),
+        (AnimationState::WalkRight, walk),
+        (AnimationState::EmitSound, Clip::once(3, 5, 0.08)),
+        (AnimationState::Death, Clip::once(6, 7, 0.2)),
+    ])
+}
+
+fn art_faces_left(_: EntityInstance) -> Facing {
+    Facing::art_faces_left()
 }
 
 fn player_movement_force(_: EntityInstance) -> MovementForce {
@@ -213,3 +535,42 @@ fn player_collider(_: EntityInstance) -> ColliderBundle {
         ..Default::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dots_bounce_off_walls_they_run_into() {
+        // A wall below the dot, its normal points up
+        let reflected = reflect_sound(Vec2::new(3., -4.), Vec2::Y).unwrap();
+
+        assert!(reflected.abs_diff_eq(Vec2::new(3., 4.), 1e-6));
+    }
+
+    #[test]
+    fn bounces_keep_the_speed() {
+        let velocity = Vec2::new(-120., 35.);
+        let reflected = reflect_sound(velocity, Vec2::new(1., 1.)).unwrap();
+
+        assert!((reflected.length() - velocity.length()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn head_on_bounces_go_straight_back() {
+        let reflected = reflect_sound(Vec2::new(50., 0.), Vec2::new(-2., 0.)).unwrap();
+
+        assert!(reflected.abs_diff_eq(Vec2::new(-50., 0.), 1e-6));
+    }
+
+    #[test]
+    fn dots_moving_away_or_sliding_along_walls_dont_bounce() {
+        assert_eq!(reflect_sound(Vec2::new(3., 4.), Vec2::Y), None);
+        assert_eq!(reflect_sound(Vec2::new(5., 0.), Vec2::Y), None);
+    }
+
+    #[test]
+    fn contacts_without_a_normal_dont_bounce() {
+        assert_eq!(reflect_sound(Vec2::new(3., -4.), Vec2::ZERO), None);
+    }
+}
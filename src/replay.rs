@@ -0,0 +1,295 @@
+//! Recording and replaying the player's input.
+//!
+//! Recordings have no RNG seed: nothing in the game is random yet.
+//! Once something is, its seed belongs in `Recording` next to the level.
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+
+use crate::{
+    game_state::{GameState, PauseState},
+    hot_reload::respawn_world,
+    input::{latch_tick_actions, Action, Actions, TickActions},
+    resources::{CurrentLevel, WorldAssets},
+    timestep::{fixed_timestep, FIXED_UPDATE, INPUT_SUBSTAGE},
+};
+
+/// Where recordings are written to and replayed from.
+pub const REPLAY_PATH: &str = "replay.json";
+
+/// Records the player's `TickActions` tick by tick and plays them back.
+///
+/// Both start from a freshly respawned level and everything that moves
+/// runs on the fixed timestep, so a replay goes through the same
+/// `player_movement` and `player_sound` path on the same ticks and ends up
+/// where the recording did, at any frame rate.
+/// F5 starts and stops recording to `REPLAY_PATH`, F6 replays it.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replay>()
+            .add_event::<ReplayEvent>()
+            .add_system(replay_hotkeys.run_in_state(GameState::Playing))
+            .add_system(
+                handle_replay_events
+                    .run_in_state(GameState::Playing)
+                    .after(replay_hotkeys),
+            )
+            .add_system(start_session_on_level_spawn)
+            .add_fixed_timestep_system_set(
+                FIXED_UPDATE,
+                INPUT_SUBSTAGE,
+                ConditionSet::new()
+                    .run_in_state(PauseState::Running)
+                    .after(latch_tick_actions)
+                    .with_system(record_actions)
+                    .with_system(play_actions)
+                    .into(),
+            );
+    }
+}
+
+/// The `TickActions` of one tick.
+///
+/// `Action::Pause` is left out, pausing during a replay would stop it for good.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct RecordedTick {
+    pub movement: Vec2,
+    pub cursor_world_position: Option<Vec2>,
+    pub pressed: Vec<Action>,
+    pub just_pressed: Vec<Action>,
+}
+
+impl RecordedTick {
+    fn capture(actions: &Actions) -> Self {
+        let recorded = || {
+            Action::ALL
+                .into_iter()
+                .filter(|&action| action != Action::Pause)
+        };
+
+        Self {
+            movement: actions.movement,
+            cursor_world_position: actions.cursor_world_position,
+            pressed: recorded()
+                .filter(|&action| actions.pressed(action))
+                .collect(),
+            just_pressed: recorded()
+                .filter(|&action| actions.just_pressed(action))
+                .collect(),
+        }
+    }
+
+    fn apply(&self, actions: &mut Actions) {
+        actions.movement = self.movement;
+        actions.cursor_world_position = self.cursor_world_position;
+
+        for action in Action::ALL {
+            if action != Action::Pause {
+                actions.set(
+                    action,
+                    self.pressed.contains(&action),
+                    self.just_pressed.contains(&action),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub level_iid: String,
+    pub timestep: f32,
+    /// One for every tick since the level respawned.
+    pub ticks: Vec<RecordedTick>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// A recording being made or played back.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub recording: Recording,
+    /// Next tick to play back.
+    pub tick: usize,
+    /// Nothing is recorded or played until the level has respawned.
+    pub started: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum Replay {
+    #[default]
+    Idle,
+    Recording(Session),
+    Playing(Session),
+}
+
+pub enum ReplayEvent {
+    StartRecording,
+    /// Writes the recording to `REPLAY_PATH`.
+    StopRecording,
+    StartReplay(Recording),
+    StopReplay,
+}
+
+fn replay_hotkeys(
+    keyboard: Res<Input<KeyCode>>,
+    replay: Res<Replay>,
+    mut replay_events: EventWriter<ReplayEvent>,
+) {
+    if keyboard.just_pressed(KeyCode::F5) {
+        match *replay {
+            Replay::Idle => replay_events.send(ReplayEvent::StartRecording),
+            Replay::Recording(_) => replay_events.send(ReplayEvent::StopRecording),
+            Replay::Playing(_) => {}
+        }
+    }
+
+    if keyboard.just_pressed(KeyCode::F6) {
+        match *replay {
+            Replay::Idle => match Recording::load(REPLAY_PATH) {
+                Ok(recording) => replay_events.send(ReplayEvent::StartReplay(recording)),
+                Err(error) => warn!("Couldn't load {}: {}", REPLAY_PATH, error),
+            },
+            Replay::Playing(_) => replay_events.send(ReplayEvent::StopReplay),
+            Replay::Recording(_) => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_replay_events(
+    mut commands: Commands,
+    mut replay_events: EventReader<ReplayEvent>,
+    mut replay: ResMut<Replay>,
+    current_level: Res<CurrentLevel>,
+    assets: Res<WorldAssets>,
+    world_query: Query<(Entity, &Handle<LdtkAsset>)>,
+) {
+    for event in replay_events.iter() {
+        let recording = match event {
+            ReplayEvent::StartRecording => match &current_level.0 {
+                Some(level_iid) => Recording {
+                    level_iid: level_iid.clone(),
+                    timestep: fixed_timestep().as_secs_f32(),
+                    ticks: Vec::new(),
+                },
+                None => continue,
+            },
+            ReplayEvent::StartReplay(recording) => recording.clone(),
+            ReplayEvent::StopRecording | ReplayEvent::StopReplay => {
+                end_session(&mut replay);
+                continue;
+            }
+        };
+
+        // Only one session at a time
+        end_session(&mut replay);
+
+        if recording.timestep != fixed_timestep().as_secs_f32() {
+            warn!(
+                "The replay was recorded with a different timestep, it may not play out the same"
+            );
+        }
+
+        commands.insert_resource(LevelSelection::Iid(recording.level_iid.clone()));
+        respawn_world(&mut commands, &world_query, &assets.map);
+
+        let session = Session {
+            recording,
+            tick: 0,
+            started: false,
+        };
+
+        *replay = match event {
+            ReplayEvent::StartRecording => {
+                info!("Recording input");
+                Replay::Recording(session)
+            }
+            _ => {
+                info!("Replaying {} ticks", session.recording.ticks.len());
+                Replay::Playing(session)
+            }
+        };
+    }
+}
+
+/// Saves a finished recording and goes back to normal input.
+fn end_session(replay: &mut Replay) {
+    match std::mem::take(replay) {
+        Replay::Idle => {}
+        Replay::Recording(session) => match session.recording.save(REPLAY_PATH) {
+            Ok(()) => info!(
+                "Saved {} ticks to {}",
+                session.recording.ticks.len(),
+                REPLAY_PATH
+            ),
+            Err(error) => error!("Couldn't save the recording: {}", error),
+        },
+        Replay::Playing(_) => info!("Replay finished"),
+    }
+}
+
+fn start_session_on_level_spawn(
+    mut level_events: EventReader<LevelEvent>,
+    mut replay: ResMut<Replay>,
+) {
+    let session = match &mut *replay {
+        Replay::Recording(session) | Replay::Playing(session) => session,
+        Replay::Idle => return,
+    };
+
+    for level_event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = level_event {
+            if *iid == session.recording.level_iid {
+                session.started = true;
+            }
+        }
+    }
+}
+
+fn record_actions(mut replay: ResMut<Replay>, actions: Res<TickActions>) {
+    if let Replay::Recording(session) = &mut *replay {
+        if session.started {
+            session
+                .recording
+                .ticks
+                .push(RecordedTick::capture(&actions));
+        }
+    }
+}
+
+fn play_actions(mut replay: ResMut<Replay>, mut actions: ResMut<TickActions>) {
+    let finished = match &mut *replay {
+        Replay::Playing(session) if session.started => {
+            match session.recording.ticks.get(session.tick) {
+                Some(tick) => {
+                    tick.apply(&mut actions.0);
+                    session.tick += 1;
+                    false
+                }
+                None => true,
+            }
+        }
+        _ => false,
+    };
+
+    if finished {
+        end_session(&mut replay);
+    }
+}
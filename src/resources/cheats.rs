@@ -0,0 +1,10 @@
+/// Shortcuts for playtesting, switched on from the developer console.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Cheats {
+    /// Mutants can't catch the player.
+    pub god: bool,
+    /// The player walks through walls.
+    pub noclip: bool,
+    /// The invisible collider layer of the level is drawn.
+    pub reveal_all: bool,
+}
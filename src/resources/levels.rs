@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use std::collections::HashSet;
+
+/// Iid of the level that was spawned last.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CurrentLevel(pub Option<String>);
+
+/// Iids of the levels the player has finished, see `LevelExitPlugin`.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CompletedLevels(pub HashSet<String>);
+
+pub fn track_current_level(
+    mut level_events: EventReader<LevelEvent>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    for level_event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = level_event {
+            current_level.0 = Some(iid.clone());
+        }
+    }
+}
@@ -1,5 +1,9 @@
+pub mod cheats;
+pub mod levels;
 pub mod textures;
 pub mod worlds;
 
+pub use cheats::*;
+pub use levels::*;
 pub use textures::*;
 pub use worlds::*;
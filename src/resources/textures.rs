@@ -6,6 +6,9 @@ pub struct TextureAssets {
     #[asset(texture_atlas(tile_size_x = 32., tile_size_y = 32., columns = 8, rows = 1))]
     #[asset(path = "player.png")]
     pub player: Handle<TextureAtlas>,
+    #[asset(texture_atlas(tile_size_x = 32., tile_size_y = 32., columns = 4, rows = 1))]
+    #[asset(path = "mutant.png")]
+    pub mutant: Handle<TextureAtlas>,
     #[asset(texture_atlas(tile_size_x = 32., tile_size_y = 32., columns = 2, rows = 1))]
     #[asset(path = "tiles.png")]
     pub tiles: Handle<TextureAtlas>,
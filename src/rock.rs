@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::prelude::*;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    components::{physics::*, wall_collisions::WallCollider},
+    game_state::PauseState,
+    graphics::y_sort::YSort,
+    input::{Action, TickActions},
+    player::{spawn_sound_waves, Player, SoundWave, SoundWaveEvent},
+    spatial_index::{SpatialIndex, SpatialIndexed},
+    timestep::{Interpolated, FIXED_UPDATE, GAMEPLAY_SUBSTAGE},
+};
+
+pub struct RockPlugin;
+
+impl Plugin for RockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep_system_set(
+            FIXED_UPDATE,
+            GAMEPLAY_SUBSTAGE,
+            ConditionSet::new()
+                .run_in_state(PauseState::Running)
+                .with_system(pick_up_or_throw_rock)
+                .with_system(carry_rock.after(pick_up_or_throw_rock))
+                .with_system(rock_wall_impacts.before(spawn_sound_waves))
+                .into(),
+        );
+    }
+}
+
+/// How close the player has to be to pick up a rock.
+const PICK_UP_RADIUS: f32 = 16.;
+const THROW_SPEED: f32 = 150.;
+/// Below this speed a thrown rock has landed and won't make a noise anymore.
+const LANDING_SPEED: f32 = 10.;
+/// Where a carried rock sits relative to the player.
+/// Slightly below the player's center so `YSort` draws it in front.
+const CARRY_OFFSET: Vec2 = Vec2::new(0., -1.);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+#[cfg_attr(feature = "inspector", derive(Inspectable))]
+pub struct Rock;
+
+/// The rock is in the player's hands.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Carried;
+
+/// The rock is flying and will make a noise when it first hits a wall,
+/// until it slows down below `LANDING_SPEED`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Thrown;
+
+#[derive(Bundle, Default, LdtkEntity)]
+pub struct RockBundle {
+    rock: Rock,
+    #[with(rock_collider)]
+    #[bundle]
+    collider: ColliderBundle,
+    #[with(rock_sprite)]
+    #[bundle]
+    sprite_bundle: SpriteBundle,
+    y_sort: YSort,
+    interpolated: Interpolated,
+    spatial_indexed: SpatialIndexed,
+}
+
+fn rock_collider(_: EntityInstance) -> ColliderBundle {
+    ColliderBundle {
+        collider: Collider::ball(3.),
+        rigid_body: RigidBody::Dynamic,
+        rotation_constraints: LockedAxes::ROTATION_LOCKED,
+        damping: Damping {
+            linear_damping: 4.0,
+            ..Default::default()
+        },
+        restitution: Restitution::coefficient(0.3),
+        collision_groups: rock_collision_groups(),
+        ..Default::default()
+    }
+}
+
+fn rock_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(ITEM_PHYS_LAYER, WALL_PHYS_LAYER | MUTANT_PHYS_LAYER)
+}
+
+fn rock_sprite(_: EntityInstance) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: Color::GRAY,
+            custom_size: Some(Vec2::splat(6.)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn pick_up_or_throw_rock(
+    mut commands: Commands,
+    actions: Res<TickActions>,
+    index: Res<SpatialIndex>,
+    player_query: Query<Entity, With<Player>>,
+    free_rock_query: Query<(), (With<Rock>, Without<Carried>)>,
+    mut carried_rock_query: Query<(Entity, &mut Velocity), (With<Rock>, With<Carried>)>,
+) {
+    if !actions.just_pressed(Action::Throw) {
+        return;
+    }
+
+    let player_position = match player_query
+        .get_single()
+        .ok()
+        .and_then(|player| index.position(player))
+    {
+        Some(position) => position,
+        None => return,
+    };
+
+    if let Ok((rock, mut velocity)) = carried_rock_query.get_single_mut() {
+        // Throw towards the cursor, or the way the player is walking without a mouse
+        let direction = actions
+            .cursor_world_position
+            .map_or(actions.movement, |cursor| cursor - player_position)
+            .try_normalize();
+
+        if let Some(direction) = direction {
+            velocity.linvel = direction * THROW_SPEED;
+            commands
+                .entity(rock)
+                .remove::<Carried>()
+                .insert(Thrown)
+                .insert(RigidBody::Dynamic)
+                .insert(rock_collision_groups());
+        }
+        return;
+    }
+
+    let closest_rock = index
+        .in_radius(player_position, PICK_UP_RADIUS)
+        .filter(|&entity| free_rock_query.contains(entity))
+        .filter_map(|rock| Some((rock, index.position(rock)?.distance(player_position))))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    if let Some((rock, _)) = closest_rock {
+        commands
+            .entity(rock)
+            .remove::<Thrown>()
+            .insert(Carried)
+            .insert(RigidBody::KinematicPositionBased)
+            .insert(CollisionGroups::new(ITEM_PHYS_LAYER, Group::empty()));
+    }
+}
+
+/// Keeps a carried rock in the player's hands.
+///
+/// Rocks and the player are both spawned on the `Entities` layer,
+/// so their local transforms share the same space.
+fn carry_rock(
+    player_query: Query<&Transform, With<Player>>,
+    mut rock_query: Query<&mut Transform, (With<Carried>, Without<Player>)>,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        for mut rock_transform in rock_query.iter_mut() {
+            let position = player_transform.translation.truncate() + CARRY_OFFSET;
+            rock_transform.translation = position.extend(rock_transform.translation.z);
+        }
+    }
+}
+
+/// Makes a sound where a thrown rock first hits a wall.
+///
+/// Same contact check as the sound dots bouncing off walls.
+/// A rock that slows down without hitting anything lands and stays silent.
+fn rock_wall_impacts(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    rock_query: Query<(Entity, &GlobalTransform, &Velocity), With<Thrown>>,
+    wall_query: Query<Entity, With<WallCollider>>,
+    mut wave_events: EventWriter<SoundWaveEvent>,
+) {
+    for (rock, transform, velocity) in rock_query.iter() {
+        if let Some(point) = wall_contact_point(&rapier_context, rock, &wall_query) {
+            wave_events.send(SoundWaveEvent {
+                origin: point.extend(transform.translation().z),
+                wave: SoundWave::IMPACT,
+            });
+            commands.entity(rock).remove::<Thrown>();
+        } else if velocity.linvel.length() < LANDING_SPEED {
+            commands.entity(rock).remove::<Thrown>();
+        }
+    }
+}
+
+/// Where the rock touches a wall, in world space.
+fn wall_contact_point(
+    rapier_context: &RapierContext,
+    rock: Entity,
+    wall_query: &Query<Entity, With<WallCollider>>,
+) -> Option<Vec2> {
+    for contact in rapier_context.contacts_with(rock) {
+        let other_collider = if contact.collider1() == rock {
+            contact.collider2()
+        } else {
+            contact.collider1()
+        };
+        if !contact.has_any_active_contacts() || !wall_query.contains(other_collider) {
+            continue;
+        }
+
+        for manifold in contact.manifolds() {
+            if let Some(solver_contact) = manifold.solver_contacts().next() {
+                // Rapier works in meters
+                return Some(solver_contact.point() * rapier_context.physics_scale());
+            }
+        }
+    }
+
+    None
+}
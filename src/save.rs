@@ -0,0 +1,223 @@
+use anyhow::{anyhow, bail};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+
+use crate::{
+    game_state::{GameState, PauseState},
+    graphics::camera_zoom::ZoomSetting,
+    player::{PendingPlayerPosition, Player},
+    resources::{CompletedLevels, CurrentLevel},
+};
+
+/// Where the game is saved, relative to the working directory.
+pub const SAVE_PATH: &str = "save.json";
+
+/// Bumped whenever `SaveGame` changes in a way old saves can't be read as.
+/// Older saves are upgraded in `SaveGame::migrate`.
+pub const SAVE_VERSION: u64 = 1;
+
+/// Saves the game when it's paused or closed,
+/// and reads the save back for the main menu's continue button.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadedSave>()
+            .add_enter_system(GameState::MainMenu, read_save)
+            .add_enter_system(PauseState::Paused, save_on_pause)
+            .add_system_to_stage(CoreStage::Last, save_on_exit);
+    }
+}
+
+/// Settings that are kept together with the progress.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub zoom: ZoomSetting,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u64,
+    pub current_level: String,
+    pub completed_levels: Vec<String>,
+    /// Relative to the current level, `None` starts at the level's spawn.
+    pub player_position: Option<Vec3>,
+    pub settings: Settings,
+}
+
+impl SaveGame {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let save: serde_json::Value = serde_json::from_str(&json)?;
+        let version = save
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| anyhow!("save has no version"))?;
+
+        if version > SAVE_VERSION {
+            bail!(
+                "save version {} is newer than this game's ({})",
+                version,
+                SAVE_VERSION
+            );
+        }
+
+        Ok(serde_json::from_value(Self::migrate(save, version)?)?)
+    }
+
+    /// Upgrades a save written by an older version to the current schema.
+    fn migrate(save: serde_json::Value, version: u64) -> anyhow::Result<serde_json::Value> {
+        match version {
+            SAVE_VERSION => Ok(save),
+            _ => bail!("save version {} is no longer supported", version),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// The save found on disk when the main menu opened, if it could be read.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct LoadedSave(pub Option<SaveGame>);
+
+fn read_save(mut loaded_save: ResMut<LoadedSave>) {
+    loaded_save.0 = match SaveGame::load(SAVE_PATH) {
+        Ok(save) => Some(save),
+        Err(error) => {
+            info!("No save to continue from: {}", error);
+            None
+        }
+    };
+}
+
+/// Picks up the game from `save`, the level is respawned if it changed.
+pub fn apply_save(commands: &mut Commands, save: &SaveGame) {
+    commands.insert_resource(LevelSelection::Iid(save.current_level.clone()));
+    commands.insert_resource(CompletedLevels(
+        save.completed_levels.iter().cloned().collect(),
+    ));
+    commands.insert_resource(save.settings.zoom);
+
+    if let Some(translation) = save.player_position {
+        commands.insert_resource(PendingPlayerPosition {
+            level_iid: save.current_level.clone(),
+            translation,
+        });
+    }
+}
+
+fn current_save(
+    current_level: &CurrentLevel,
+    completed_levels: &CompletedLevels,
+    zoom: ZoomSetting,
+    player_query: &Query<&Transform, With<Player>>,
+) -> Option<SaveGame> {
+    let current_level = current_level.0.clone()?;
+
+    let mut completed_levels: Vec<String> = completed_levels.0.iter().cloned().collect();
+    completed_levels.sort();
+
+    Some(SaveGame {
+        version: SAVE_VERSION,
+        current_level,
+        completed_levels,
+        player_position: player_query
+            .get_single()
+            .ok()
+            .map(|transform| transform.translation),
+        settings: Settings { zoom },
+    })
+}
+
+fn write_save(save: Option<SaveGame>) {
+    if let Some(save) = save {
+        match save.save(SAVE_PATH) {
+            Ok(()) => info!("Saved the game to {}", SAVE_PATH),
+            Err(error) => error!("Couldn't save the game: {}", error),
+        }
+    }
+}
+
+fn save_on_pause(
+    game_state: Res<CurrentState<GameState>>,
+    current_level: Res<CurrentLevel>,
+    completed_levels: Res<CompletedLevels>,
+    zoom: Res<ZoomSetting>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    // The main menu pauses the game too, there's nothing to save yet
+    if game_state.0 == GameState::Playing {
+        write_save(current_save(
+            &current_level,
+            &completed_levels,
+            *zoom,
+            &player_query,
+        ));
+    }
+}
+
+fn save_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    game_state: Res<CurrentState<GameState>>,
+    current_level: Res<CurrentLevel>,
+    completed_levels: Res<CompletedLevels>,
+    zoom: Res<ZoomSetting>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    if exit_events.iter().last().is_some() && game_state.0 == GameState::Playing {
+        write_save(current_save(
+            &current_level,
+            &completed_levels,
+            *zoom,
+            &player_query,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bevy::ecs::system::{CommandQueue, SystemState};
+
+    use std::collections::HashSet;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn completed_levels_survive_saving_and_loading() {
+        let completed_levels = CompletedLevels(HashSet::from(["first".into(), "second".into()]));
+        let mut world = World::new();
+        let mut player_query = SystemState::<Query<&Transform, With<Player>>>::new(&mut world);
+
+        let save = current_save(
+            &CurrentLevel(Some("third".into())),
+            &completed_levels,
+            ZoomSetting::default(),
+            &player_query.get(&world),
+        )
+        .expect("a level is spawned");
+
+        let path = env::temp_dir().join(format!("graveyard_shift_save_{}.json", process::id()));
+        let path = path.to_str().expect("the temp dir is valid unicode");
+        save.save(path).unwrap();
+        let loaded = SaveGame::load(path);
+        fs::remove_file(path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, save);
+
+        let mut queue = CommandQueue::default();
+        apply_save(&mut Commands::new(&mut queue, &world), &loaded);
+        queue.apply(&mut world);
+        assert_eq!(*world.resource::<CompletedLevels>(), completed_levels);
+    }
+}
@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use iyes_loopless::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::timestep::{FIXED_UPDATE, TICK_END_SUBSTAGE};
+
+/// Cell size until the first level is spawned, the usual LDtk grid size.
+const DEFAULT_CELL_SIZE: f32 = 16.;
+
+/// Keeps the `SpatialIndex` up to date.
+///
+/// Cells are as big as the tiles of the current level.
+/// At the end of every tick only the entities that moved change cells,
+/// it's only rebuilt from scratch when a level spawns.
+/// Despawned entities are removed at the end of the frame,
+/// whether or not a tick ran.
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialIndex>()
+            .add_system(resize_cells_on_level_spawn)
+            .add_fixed_timestep_system(FIXED_UPDATE, TICK_END_SUBSTAGE, update_spatial_index)
+            .add_system_to_stage(CoreStage::Last, remove_despawned_entities);
+    }
+}
+
+/// Puts the entity in the `SpatialIndex`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct SpatialIndexed;
+
+/// Finds `SpatialIndexed` entities near a point without going through all of them.
+///
+/// Positions are in world space and where the last tick left the entities.
+/// `GlobalTransform`s lag a tick behind inside `FIXED_UPDATE`,
+/// so gameplay should take positions from here with `position`.
+#[derive(Clone, Debug)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+    entity_cells: HashMap<Entity, (IVec2, Vec2)>,
+    /// Everything gets inserted again on the next update.
+    needs_rebuild: bool,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self {
+            cell_size: DEFAULT_CELL_SIZE,
+            cells: HashMap::new(),
+            entity_cells: HashMap::new(),
+            needs_rebuild: true,
+        }
+    }
+}
+
+impl SpatialIndex {
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    /// How many entities are in the index.
+    pub fn len(&self) -> usize {
+        self.entity_cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entity_cells.is_empty()
+    }
+
+    /// Where the entity was at the end of the last tick.
+    pub fn position(&self, entity: Entity) -> Option<Vec2> {
+        self.entity_cells
+            .get(&entity)
+            .map(|&(_, position)| position)
+    }
+
+    /// Entities in every cell the circle touches,
+    /// some of them can be up to a cell further away than `radius`.
+    pub fn within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        let min = self.cell(center - Vec2::splat(radius));
+        let max = self.cell(center + Vec2::splat(radius));
+
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    /// Entities at most `radius` away from `center`.
+    pub fn in_radius(&self, center: Vec2, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        self.within(center, radius).filter(move |entity| {
+            self.position(*entity)
+                .map_or(false, |position| position.distance(center) <= radius)
+        })
+    }
+
+    /// Adds the entity, or moves it if it's already in the index.
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        let cell = self.cell(position);
+
+        match self.entity_cells.insert(entity, (cell, position)) {
+            Some((old_cell, _)) if old_cell == cell => return,
+            Some((old_cell, _)) => self.remove_from_cell(entity, old_cell),
+            None => {}
+        }
+
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some((cell, _)) = self.entity_cells.remove(&entity) {
+            self.remove_from_cell(entity, cell);
+        }
+    }
+
+    /// Empties the index and switches to cells of `cell_size`.
+    fn reset(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.cells.clear();
+        self.entity_cells.clear();
+        self.needs_rebuild = true;
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, cell: IVec2) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|&other| other != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+}
+
+fn resize_cells_on_level_spawn(
+    mut level_events: EventReader<LevelEvent>,
+    mut index: ResMut<SpatialIndex>,
+    level_query: Query<&Handle<LdtkLevel>>,
+    levels: Res<Assets<LdtkLevel>>,
+) {
+    for level_event in level_events.iter() {
+        if let LevelEvent::Spawned(iid) = level_event {
+            let grid_size = level_query
+                .iter()
+                .filter_map(|handle| levels.get(handle))
+                .find(|level| level.level.iid == *iid)
+                .and_then(|level| level.level.layer_instances.as_ref()?.first())
+                .map(|layer_instance| layer_instance.grid_size);
+
+            if let Some(grid_size) = grid_size {
+                index.reset(grid_size as f32);
+            }
+        }
+    }
+}
+
+/// Where the entity is after this tick, its parent doesn't move during ticks.
+fn world_position(
+    transform: &Transform,
+    parent: Option<&Parent>,
+    parent_query: &Query<&GlobalTransform>,
+) -> Vec2 {
+    let parent_transform = parent.and_then(|parent| parent_query.get(parent.get()).ok());
+    match parent_transform {
+        Some(parent_transform) => parent_transform.mul_transform(*transform).translation(),
+        None => transform.translation,
+    }
+    .truncate()
+}
+
+#[allow(clippy::type_complexity)]
+fn update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    moved_query: Query<
+        (Entity, &Transform, Option<&Parent>),
+        (With<SpatialIndexed>, Changed<Transform>),
+    >,
+    indexed_query: Query<(Entity, &Transform, Option<&Parent>), With<SpatialIndexed>>,
+    parent_query: Query<&GlobalTransform>,
+) {
+    if index.needs_rebuild {
+        index.needs_rebuild = false;
+        for (entity, transform, parent) in indexed_query.iter() {
+            index.insert(entity, world_position(transform, parent, &parent_query));
+        }
+    } else {
+        for (entity, transform, parent) in moved_query.iter() {
+            index.insert(entity, world_position(transform, parent, &parent_query));
+        }
+    }
+}
+
+/// Runs every frame, ticks don't see every removal.
+fn remove_despawned_entities(
+    mut index: ResMut<SpatialIndex>,
+    removed: RemovedComponents<SpatialIndexed>,
+) {
+    for entity in removed.iter() {
+        index.remove(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn index() -> SpatialIndex {
+        let mut index = SpatialIndex::default();
+        index.reset(16.);
+        index
+    }
+
+    fn within(index: &SpatialIndex, center: Vec2, radius: f32) -> HashSet<Entity> {
+        index.within(center, radius).collect()
+    }
+
+    #[test]
+    fn cells_round_towards_negative_infinity() {
+        let index = index();
+        assert_eq!(index.cell(Vec2::new(0., 15.9)), IVec2::new(0, 0));
+        assert_eq!(index.cell(Vec2::new(-0.1, 16.)), IVec2::new(-1, 1));
+        assert_eq!(index.cell(Vec2::new(-16., -16.1)), IVec2::new(-1, -2));
+    }
+
+    #[test]
+    fn moving_entities_change_cells() {
+        let mut index = index();
+        let entity = Entity::from_raw(0);
+
+        index.insert(entity, Vec2::new(8., 8.));
+        index.insert(entity, Vec2::new(100., 8.));
+
+        assert_eq!(index.len(), 1);
+        assert!(within(&index, Vec2::new(8., 8.), 1.).is_empty());
+        assert_eq!(
+            within(&index, Vec2::new(100., 8.), 1.),
+            HashSet::from([entity])
+        );
+
+        // Moving within the same cell doesn't add it twice
+        index.insert(entity, Vec2::new(101., 9.));
+        assert_eq!(index.within(Vec2::new(100., 8.), 1.).count(), 1);
+        assert_eq!(index.position(entity), Some(Vec2::new(101., 9.)));
+    }
+
+    #[test]
+    fn removed_entities_are_gone() {
+        let mut index = index();
+        let removed = Entity::from_raw(0);
+        let kept = Entity::from_raw(1);
+
+        index.insert(removed, Vec2::new(8., 8.));
+        index.insert(kept, Vec2::new(9., 9.));
+        index.remove(removed);
+        // Removing twice is fine, despawns can be reported more than once
+        index.remove(removed);
+
+        assert_eq!(index.len(), 1);
+        assert_eq!(within(&index, Vec2::new(8., 8.), 1.), HashSet::from([kept]));
+
+        index.remove(kept);
+        assert!(index.is_empty());
+        assert!(index.cells.is_empty(), "empty cells are left behind");
+    }
+
+    #[test]
+    fn within_covers_every_cell_the_circle_touches() {
+        let mut index = index();
+        let entities: Vec<_> = (0..5).map(Entity::from_raw).collect();
+
+        // Around the origin, so the circle spans negative and positive cells
+        index.insert(entities[0], Vec2::new(-20., -20.));
+        index.insert(entities[1], Vec2::new(20., -1.));
+        index.insert(entities[2], Vec2::new(-1., 20.));
+        index.insert(entities[3], Vec2::new(0., 0.));
+        // Outside every cell the circle touches
+        index.insert(entities[4], Vec2::new(-40., 0.));
+
+        assert_eq!(
+            within(&index, Vec2::ZERO, 20.),
+            HashSet::from([entities[0], entities[1], entities[2], entities[3]])
+        );
+        assert_eq!(
+            within(&index, Vec2::new(-20., -20.), 0.),
+            HashSet::from([entities[0]])
+        );
+        assert_eq!(
+            within(&index, Vec2::new(-40., 8.), 10.),
+            HashSet::from([entities[4]])
+        );
+    }
+
+    #[test]
+    fn in_radius_checks_the_exact_distance() {
+        let mut index = index();
+        let close = Entity::from_raw(0);
+        let same_cell = Entity::from_raw(1);
+
+        index.insert(close, Vec2::new(-3., 4.));
+        // Shares a cell with the circle, but is too far away
+        index.insert(same_cell, Vec2::new(-15., 15.));
+
+        assert_eq!(
+            within(&index, Vec2::ZERO, 5.),
+            HashSet::from([close, same_cell])
+        );
+        assert_eq!(
+            index.in_radius(Vec2::ZERO, 5.).collect::<HashSet<_>>(),
+            HashSet::from([close])
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use bevy::ecs::event::Event;
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+
+use std::time::Duration;
+
+use crate::utils::asset_loader_ldtk::apply_ldtk_texture_assets;
+
+/// Label of the fixed timestep stage gameplay and physics run in.
+pub const FIXED_UPDATE: &str = "fixed_update";
+
+/// Substages of `FIXED_UPDATE`, every tick runs them in this order.
+///
+/// `INPUT_SUBSTAGE` hands the input collected since the last tick over
+/// as `TickActions`, gameplay reads it in `GAMEPLAY_SUBSTAGE`.
+pub const INPUT_SUBSTAGE: usize = 0;
+pub const GAMEPLAY_SUBSTAGE: usize = 1;
+const PHYSICS_SYNC_SUBSTAGE: usize = 2;
+const PHYSICS_STEP_SUBSTAGE: usize = 3;
+const PHYSICS_WRITEBACK_SUBSTAGE: usize = 4;
+/// Runs after physics, everything in it sees where the tick left things.
+pub const TICK_END_SUBSTAGE: usize = 5;
+
+/// How often gameplay and physics tick, independent of the frame rate.
+pub fn fixed_timestep() -> Duration {
+    Duration::from_nanos(1_000_000_000 / 60)
+}
+
+/// Slows everything in `FIXED_UPDATE` down, or speeds it up above 1.
+///
+/// Only changes how often it ticks, every tick still covers `fixed_timestep`
+/// so the simulation itself stays the same.
+pub fn set_time_scale(timesteps: &mut FixedTimesteps, scale: f32) {
+    if let Some(info) = timesteps.get_mut(FIXED_UPDATE) {
+        info.step = fixed_timestep().div_f32(scale);
+    }
+}
+
+/// Runs gameplay and rapier at `fixed_timestep` instead of once per frame,
+/// so movement and sound dots behave the same at any frame rate.
+///
+/// A slow frame runs several ticks, a fast one may run none.
+/// `Interpolated` entities are drawn between their last two ticks
+/// so they still move smoothly on screen.
+///
+/// Everything that changes the state of the game goes into
+/// `GAMEPLAY_SUBSTAGE` and advances by `fixed_timestep` instead of
+/// reading `Time`. Only collecting input and drawing stay in the
+/// per frame stages.
+pub struct TimestepPlugin;
+
+impl Plugin for TimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_fixed_timestep(fixed_timestep(), FIXED_UPDATE)
+            .add_fixed_timestep_child_stage(FIXED_UPDATE)
+            .add_fixed_timestep_child_stage(FIXED_UPDATE)
+            .add_fixed_timestep_child_stage(FIXED_UPDATE)
+            .add_fixed_timestep_child_stage(FIXED_UPDATE)
+            .add_fixed_timestep_child_stage(FIXED_UPDATE)
+            .add_fixed_timestep_system_set(
+                FIXED_UPDATE,
+                PHYSICS_SYNC_SUBSTAGE,
+                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsStages::SyncBackend),
+            )
+            .add_fixed_timestep_system_set(
+                FIXED_UPDATE,
+                PHYSICS_STEP_SUBSTAGE,
+                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsStages::StepSimulation),
+            )
+            .add_fixed_timestep_system_set(
+                FIXED_UPDATE,
+                PHYSICS_WRITEBACK_SUBSTAGE,
+                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsStages::Writeback),
+            )
+            .add_fixed_timestep_system(FIXED_UPDATE, TICK_END_SUBSTAGE, record_tick_positions)
+            .add_system_set_to_stage(
+                CoreStage::Last,
+                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsStages::DetectDespawn),
+            )
+            .add_system(move_visuals_to_child.after(apply_ldtk_texture_assets))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_positions.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Events that are sent and read within `FIXED_UPDATE`.
+pub trait FixedTimestepEventAppExt {
+    /// Like `App::add_event`, but the events are kept for two ticks
+    /// instead of two frames.
+    ///
+    /// Two frames can pass without a tick at high frame rates,
+    /// so regular events sent by a tick could be gone before the next one.
+    fn add_fixed_timestep_event<T: Event>(&mut self) -> &mut Self;
+}
+
+impl FixedTimestepEventAppExt for App {
+    fn add_fixed_timestep_event<T: Event>(&mut self) -> &mut Self {
+        self.init_resource::<Events<T>>().add_fixed_timestep_system(
+            FIXED_UPDATE,
+            TICK_END_SUBSTAGE,
+            Events::<T>::update_system,
+        )
+    }
+}
+
+/// Draws the entity between where it was on the last two ticks.
+///
+/// The sprite or mesh of the entity is moved to a child, see `Visuals`,
+/// and only that child is moved to the in-between position.
+/// The entity's own `Transform` is left where the last tick put it,
+/// so physics never sees an interpolated position.
+#[derive(Copy, Clone, PartialEq, Debug, Default, Component)]
+pub struct Interpolated {
+    previous: Vec2,
+    current: Vec2,
+    /// From the tick position to where the entity is drawn this frame.
+    offset: Vec2,
+}
+
+impl Interpolated {
+    /// Where the last tick left the entity, in the space of its `Transform`.
+    pub fn current(&self) -> Vec2 {
+        self.current
+    }
+
+    /// How far the entity is drawn from its `Transform` this frame.
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+}
+
+/// The child entity that draws an `Interpolated` entity.
+///
+/// Systems changing the sprite of an `Interpolated` entity have to
+/// change it on this child instead.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Component)]
+pub struct Visuals(pub Entity);
+
+impl Visuals {
+    /// The entity the sprite of `entity` is on, itself if it has no `Visuals`.
+    pub fn of(entity: Entity, visuals: Option<&Visuals>) -> Entity {
+        visuals.map_or(entity, |visuals| visuals.0)
+    }
+}
+
+/// Runs after the texture loader, so sprite sheets are moved with their atlas.
+#[allow(clippy::type_complexity)]
+fn move_visuals_to_child(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Option<(&TextureAtlasSprite, &Handle<TextureAtlas>)>,
+            Option<(&Sprite, &Handle<Image>)>,
+            Option<(&Mesh2dHandle, &Handle<ColorMaterial>)>,
+        ),
+        (Added<Interpolated>, Without<Visuals>),
+    >,
+) {
+    for (entity, sprite_sheet, sprite, mesh) in query.iter() {
+        let mut visuals = commands.spawn_bundle(TransformBundle::default());
+        visuals
+            .insert_bundle(VisibilityBundle::default())
+            .insert(Name::new("Visuals"));
+
+        if let Some((sprite, atlas)) = sprite_sheet {
+            visuals.insert(sprite.clone()).insert(atlas.clone());
+        }
+        if let Some((sprite, image)) = sprite {
+            visuals.insert(sprite.clone()).insert(image.clone());
+        }
+        if let Some((mesh, material)) = mesh {
+            visuals.insert(mesh.clone()).insert(material.clone());
+        }
+
+        let visuals = visuals.id();
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.add_child(visuals).insert(Visuals(visuals));
+
+        if sprite_sheet.is_some() {
+            entity_commands
+                .remove::<TextureAtlasSprite>()
+                .remove::<Handle<TextureAtlas>>();
+        }
+        if sprite.is_some() {
+            entity_commands.remove::<Sprite>().remove::<Handle<Image>>();
+        }
+        if mesh.is_some() {
+            entity_commands
+                .remove::<Mesh2dHandle>()
+                .remove::<Handle<ColorMaterial>>();
+        }
+    }
+}
+
+fn record_tick_positions(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = interpolated.current;
+        interpolated.current = transform.translation.truncate();
+    }
+}
+
+pub fn interpolate_positions(
+    mut query: Query<(&Transform, &mut Interpolated, &Visuals)>,
+    mut visuals_query: Query<&mut Transform, Without<Interpolated>>,
+    timesteps: Res<FixedTimesteps>,
+) {
+    let overstep = timesteps
+        .get(FIXED_UPDATE)
+        .map_or(1., |info| info.overstep_percentage() as f32);
+
+    for (transform, mut interpolated, visuals) in query.iter_mut() {
+        let position = transform.translation.truncate();
+
+        // Something outside the ticks moved it, like a respawn, so don't drag it back
+        if position != interpolated.current {
+            interpolated.previous = position;
+            interpolated.current = position;
+        }
+
+        interpolated.offset =
+            interpolated.previous.lerp(interpolated.current, overstep) - interpolated.current;
+
+        if let Ok(mut visuals_transform) = visuals_query.get_mut(visuals.0) {
+            // Standing still shouldn't make the child propagate its transform again
+            if visuals_transform.translation.truncate() != interpolated.offset {
+                let z = visuals_transform.translation.z;
+                visuals_transform.translation = interpolated.offset.extend(z);
+            }
+        }
+    }
+}
@@ -1,9 +1,45 @@
 use bevy::prelude::*;
+use bevy_trait_query::{impl_trait_query, RegisterExt};
+use iyes_loopless::prelude::*;
 
-use crate::resources::TextureAssets;
+use crate::{mutant::Mutant, player::Player, resources::TextureAssets};
+
+/// Gives LDtk entities their sprite sheets from the preloaded `TextureAssets`.
+///
+/// Entity bundles spawn with an empty `SpriteSheetBundle` and a component
+/// implementing `LdtkTextureAssetLoader`.
+/// The atlas is filled in on the frame the entity spawns,
+/// before anything gets rendered, so sprites never pop in.
+/// It runs in `Update` so the atlas is in place before the sprites of
+/// `Interpolated` entities are moved to their `Visuals`.
+/// The world spawns behind the loading screen and main menu,
+/// so this runs in every state once the textures are loaded.
+pub struct LdtkTextureAssetPlugin;
+
+impl Plugin for LdtkTextureAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_component_as::<dyn LdtkTextureAssetLoader, Player>()
+            .register_component_as::<dyn LdtkTextureAssetLoader, Mutant>()
+            .add_system(apply_ldtk_texture_assets.run_if_resource_exists::<TextureAssets>());
+    }
+}
 
 pub trait LdtkTextureAssetLoader: 'static {
     fn get_asset(&self, assets: &Res<TextureAssets>) -> Handle<TextureAtlas>;
 }
 
-// impl_trait_query!(LdtkTextureAssetLoader);
+impl_trait_query!(LdtkTextureAssetLoader);
+
+pub fn apply_ldtk_texture_assets(
+    mut query: Query<
+        (&dyn LdtkTextureAssetLoader, &mut Handle<TextureAtlas>),
+        Added<TextureAtlasSprite>,
+    >,
+    assets: Res<TextureAssets>,
+) {
+    for (loaders, mut atlas) in query.iter_mut() {
+        if let Some(loader) = loaders.into_iter().next() {
+            *atlas = loader.get_asset(&assets);
+        }
+    }
+}
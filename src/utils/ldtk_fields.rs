@@ -0,0 +1,14 @@
+use bevy_ecs_ldtk::ldtk::{FieldInstance, FieldValue, Level};
+
+/// Finds the value of a level field by its identifier in the LDtk project.
+pub fn level_field<'a>(level: &'a Level, identifier: &str) -> Option<&'a FieldValue> {
+    find_field(&level.field_instances, identifier)
+}
+
+/// Finds the value of a field by its identifier, for both levels and entities.
+pub fn find_field<'a>(fields: &'a [FieldInstance], identifier: &str) -> Option<&'a FieldValue> {
+    fields
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .map(|field| &field.value)
+}
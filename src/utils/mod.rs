@@ -1 +1,3 @@
 pub mod asset_loader_ldtk;
+pub mod ldtk_fields;
+pub mod wall_merge;
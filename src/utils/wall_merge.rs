@@ -0,0 +1,667 @@
+//! Merging of wall tiles into as few colliders as practical.
+//!
+//! Everything in here is plain data in, plain data out, so it can be run
+//! and checked without spinning up the ECS.
+
+use bevy::math::IVec2;
+use bevy_ecs_ldtk::prelude::GridCoords;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// How the wall tiles of a level get turned into colliders.
+///
+/// Chosen per level with the `WallColliders` level field in LDtk.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
+pub enum WallDecomposition {
+    /// Merge identical plates row by row, see `merge_walls`.
+    Rows,
+    /// Repeatedly take the largest rectangle left, see `greedy_merge_walls`.
+    Greedy,
+    /// Closed chains around every wall region, see `trace_wall_outlines`.
+    ///
+    /// The default, since it has no seams between neighbouring colliders
+    /// for fast sound dots to snag on.
+    #[default]
+    Outline,
+}
+
+impl fmt::Display for WallDecomposition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WallDecomposition::Rows => write!(f, "Rows"),
+            WallDecomposition::Greedy => write!(f, "Greedy"),
+            WallDecomposition::Outline => write!(f, "Outline"),
+        }
+    }
+}
+
+impl FromStr for WallDecomposition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Rows" => Ok(WallDecomposition::Rows),
+            "Greedy" => Ok(WallDecomposition::Greedy),
+            "Outline" => Ok(WallDecomposition::Outline),
+            _ => Err(anyhow::anyhow!("Unknown wall decomposition: {}", s)),
+        }
+    }
+}
+
+/// Represents a wide wall that is 1 tile tall
+/// Used to spawn wall collisions
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
+pub struct Plate {
+    pub left: i32,
+    pub right: i32,
+}
+
+/// A simple rectangle type representing a wall of any size
+///
+/// All bounds are inclusive tile coordinates.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
+pub struct Rect {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn width(&self) -> i32 {
+        self.right - self.left + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.top - self.bottom + 1
+    }
+
+    pub fn area(&self) -> i32 {
+        self.width() * self.height()
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = GridCoords> + '_ {
+        (self.bottom..=self.top)
+            .flat_map(move |y| (self.left..=self.right).map(move |x| GridCoords { x, y }))
+    }
+}
+
+/// How well a set of wall tiles was merged.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct MergeStats {
+    pub decomposition: WallDecomposition,
+    /// Number of wall tiles in the input.
+    pub tiles: usize,
+    /// Number of colliders spawned for the walls.
+    pub colliders: usize,
+    /// Size of the largest rectangle in tiles, 0 for outlines.
+    pub largest_rect: i32,
+}
+
+impl MergeStats {
+    pub fn for_rects(
+        decomposition: WallDecomposition,
+        walls: &HashSet<GridCoords>,
+        rects: &[Rect],
+    ) -> Self {
+        Self {
+            decomposition,
+            tiles: walls.len(),
+            colliders: rects.len(),
+            largest_rect: rects.iter().map(Rect::area).max().unwrap_or(0),
+        }
+    }
+
+    pub fn for_outlines(walls: &HashSet<GridCoords>, outlines: &[Vec<IVec2>]) -> Self {
+        Self {
+            decomposition: WallDecomposition::Outline,
+            tiles: walls.len(),
+            colliders: outlines.len(),
+            largest_rect: 0,
+        }
+    }
+}
+
+impl fmt::Display for MergeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} wall tiles merged into {} colliders with {}",
+            self.tiles, self.colliders, self.decomposition
+        )?;
+        if self.largest_rect > 0 {
+            write!(f, " (largest {} tiles)", self.largest_rect)?;
+        }
+        Ok(())
+    }
+}
+
+/// Ways a set of rectangles can fail to describe a set of wall tiles.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CoverError {
+    /// A tile is covered by more than one rectangle.
+    Overlap(GridCoords),
+    /// A rectangle covers a tile that isn't a wall.
+    NotAWall(GridCoords),
+    /// A wall tile isn't covered by any rectangle.
+    Uncovered(GridCoords),
+}
+
+impl fmt::Display for CoverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoverError::Overlap(c) => write!(f, "tile ({}, {}) is covered twice", c.x, c.y),
+            CoverError::NotAWall(c) => write!(f, "tile ({}, {}) is not a wall", c.x, c.y),
+            CoverError::Uncovered(c) => write!(f, "wall ({}, {}) is not covered", c.x, c.y),
+        }
+    }
+}
+
+impl std::error::Error for CoverError {}
+
+/// Checks that `rects` cover exactly the tiles in `walls`, each one once.
+pub fn check_cover(walls: &HashSet<GridCoords>, rects: &[Rect]) -> Result<(), CoverError> {
+    let mut covered: HashSet<GridCoords> = HashSet::new();
+
+    for tile in rects.iter().flat_map(Rect::tiles) {
+        if !walls.contains(&tile) {
+            return Err(CoverError::NotAWall(tile));
+        }
+        if !covered.insert(tile) {
+            return Err(CoverError::Overlap(tile));
+        }
+    }
+
+    match walls.iter().find(|tile| !covered.contains(tile)) {
+        Some(&tile) => Err(CoverError::Uncovered(tile)),
+        None => Ok(()),
+    }
+}
+
+/// Merges the walls of a `width` by `height` level into rectangles.
+///
+/// The algorithm used here is a nice compromise between simplicity, speed,
+/// and a small number of rectangles.
+/// In basic terms, it will:
+/// 1. combine wall tiles into flat "plates" in each individual row
+/// 2. combine the plates into rectangles across multiple rows wherever possible
+pub fn merge_walls(width: i32, height: i32, walls: &HashSet<GridCoords>) -> Vec<Rect> {
+    let mut plate_stack = combine_walls_into_plates(width, height, walls);
+    fill_wall_rects(&mut plate_stack)
+}
+
+pub fn fill_wall_rects(plate_stack: &mut Vec<Vec<Plate>>) -> Vec<Rect> {
+    let mut wall_rects: Vec<Rect> = Vec::new();
+    let mut previous_rects: HashMap<Plate, Rect> = HashMap::new();
+
+    // an extra empty row so the algorithm "terminates" the rects that touch the top
+    // edge
+    plate_stack.push(Vec::new());
+
+    for (y, row) in plate_stack.iter().enumerate() {
+        let mut current_rects: HashMap<Plate, Rect> = HashMap::new();
+        for plate in row {
+            if let Some(previous_rect) = previous_rects.remove(plate) {
+                current_rects.insert(
+                    *plate,
+                    Rect {
+                        top: previous_rect.top + 1,
+                        ..previous_rect
+                    },
+                );
+            } else {
+                current_rects.insert(
+                    *plate,
+                    Rect {
+                        bottom: y as i32,
+                        top: y as i32,
+                        left: plate.left,
+                        right: plate.right,
+                    },
+                );
+            }
+        }
+
+        // Any plates that weren't removed above have terminated
+        wall_rects.append(&mut previous_rects.values().copied().collect());
+        previous_rects = current_rects;
+    }
+
+    wall_rects
+}
+
+pub fn combine_walls_into_plates(
+    width: i32,
+    height: i32,
+    level_walls: &HashSet<GridCoords>,
+) -> Vec<Vec<Plate>> {
+    let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
+
+    for y in 0..height {
+        let mut row_plates: Vec<Plate> = Vec::new();
+        let mut plate_start = None;
+
+        // + 1 to the width so the algorithm "terminates" plates that touch the right
+        // edge
+        for x in 0..width + 1 {
+            match (plate_start, level_walls.contains(&GridCoords { x, y })) {
+                (Some(s), false) => {
+                    row_plates.push(Plate {
+                        left: s,
+                        right: x - 1,
+                    });
+                    plate_start = None;
+                }
+                (None, true) => plate_start = Some(x),
+                _ => (),
+            }
+        }
+
+        plate_stack.push(row_plates);
+    }
+
+    plate_stack
+}
+
+/// Merges walls by repeatedly cutting out the largest rectangle that is left.
+///
+/// Slower than `merge_walls`, but rooms with irregular shapes end up with
+/// far fewer, chunkier rectangles instead of many thin strips.
+pub fn greedy_merge_walls(width: i32, height: i32, walls: &HashSet<GridCoords>) -> Vec<Rect> {
+    // Tiles outside the level could never be picked and would loop forever
+    let mut remaining: HashSet<GridCoords> = walls
+        .iter()
+        .filter(|tile| (0..width).contains(&tile.x) && (0..height).contains(&tile.y))
+        .copied()
+        .collect();
+    let mut wall_rects: Vec<Rect> = Vec::new();
+
+    while let Some(rect) = largest_rect(width, height, &remaining) {
+        for tile in rect.tiles() {
+            remaining.remove(&tile);
+        }
+        wall_rects.push(rect);
+    }
+
+    wall_rects
+}
+
+/// Finds the largest rectangle made only of `walls`.
+///
+/// Builds a histogram of wall column heights for every row and finds the
+/// largest rectangle under it with the usual stack based approach.
+fn largest_rect(width: i32, height: i32, walls: &HashSet<GridCoords>) -> Option<Rect> {
+    let mut heights: Vec<i32> = vec![0; width as usize];
+    let mut best: Option<Rect> = None;
+
+    for y in 0..height {
+        for (x, column_height) in heights.iter_mut().enumerate() {
+            if walls.contains(&GridCoords { x: x as i32, y }) {
+                *column_height += 1;
+            } else {
+                *column_height = 0;
+            }
+        }
+
+        // Indices of columns with increasing heights.
+        // + 1 to the width so every column is popped by the end of the row
+        let mut stack: Vec<usize> = Vec::new();
+        for x in 0..=heights.len() {
+            let column_height = heights.get(x).copied().unwrap_or(0);
+
+            while let Some(&top) = stack.last() {
+                if heights[top] < column_height {
+                    break;
+                }
+                stack.pop();
+
+                if heights[top] > 0 {
+                    let left = stack.last().map_or(0, |&left| left + 1);
+                    let rect = Rect {
+                        left: left as i32,
+                        right: x as i32 - 1,
+                        top: y,
+                        bottom: y - heights[top] + 1,
+                    };
+                    if best.map_or(true, |best| rect.area() > best.area()) {
+                        best = Some(rect);
+                    }
+                }
+            }
+
+            stack.push(x);
+        }
+    }
+
+    best
+}
+
+/// Traces the outline of every connected wall region as closed loops of
+/// tile corners.
+///
+/// Loops go counter clockwise around walls and clockwise around holes,
+/// so the walls are always on the left.
+/// The last vertex of a loop connects back to the first one,
+/// and no loop passes the same corner twice.
+pub fn trace_wall_outlines(walls: &HashSet<GridCoords>) -> Vec<Vec<IVec2>> {
+    let is_wall = |x: i32, y: i32| walls.contains(&GridCoords { x, y });
+
+    // Every tile side that faces open space is an edge of some outline
+    let mut edges: HashMap<IVec2, Vec<IVec2>> = HashMap::new();
+    for &GridCoords { x, y } in walls {
+        let mut add_edge = |from: IVec2, to: IVec2| edges.entry(from).or_default().push(to);
+
+        if !is_wall(x, y - 1) {
+            add_edge(IVec2::new(x, y), IVec2::new(x + 1, y));
+        }
+        if !is_wall(x + 1, y) {
+            add_edge(IVec2::new(x + 1, y), IVec2::new(x + 1, y + 1));
+        }
+        if !is_wall(x, y + 1) {
+            add_edge(IVec2::new(x + 1, y + 1), IVec2::new(x, y + 1));
+        }
+        if !is_wall(x - 1, y) {
+            add_edge(IVec2::new(x, y + 1), IVec2::new(x, y));
+        }
+    }
+
+    // Sorted so the same map always produces the same outlines
+    let mut starts: Vec<IVec2> = edges.keys().copied().collect();
+    starts.sort_by_key(|corner| (corner.y, corner.x));
+
+    let mut outlines: Vec<Vec<IVec2>> = Vec::new();
+    for start in starts {
+        // Every corner has as many edges going in as going out,
+        // so walking from any edge always leads back to where it started
+        while let Some(mut current) = pop_edge(&mut edges, start) {
+            let mut outline = vec![start];
+            while current != start {
+                // Walls that only touch diagonally make the walk pass their
+                // corner twice, what's in between is a loop of its own
+                if let Some(i) = outline.iter().position(|&corner| corner == current) {
+                    outlines.push(remove_collinear_corners(outline.split_off(i)));
+                }
+                outline.push(current);
+                current = pop_edge(&mut edges, current).expect("Wall outlines should be closed");
+            }
+            outlines.push(remove_collinear_corners(outline));
+        }
+    }
+
+    outlines
+}
+
+fn pop_edge(edges: &mut HashMap<IVec2, Vec<IVec2>>, from: IVec2) -> Option<IVec2> {
+    edges.get_mut(&from).and_then(Vec::pop)
+}
+
+/// Drops the corners in the middle of straight lines of a closed outline.
+fn remove_collinear_corners(outline: Vec<IVec2>) -> Vec<IVec2> {
+    let len = outline.len();
+    (0..len)
+        .filter(|&i| {
+            let previous = outline[(i + len - 1) % len];
+            let current = outline[i];
+            let next = outline[(i + 1) % len];
+            let vertical = previous.x == current.x && current.x == next.x;
+            let horizontal = previous.y == current.y && current.y == next.y;
+            !(vertical || horizontal)
+        })
+        .map(|i| outline[i])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Enough random levels to hit odd shapes without slowing the tests down.
+    const RANDOM_LEVELS: usize = 500;
+
+    /// Xorshift, so the random levels are the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, max: u64) -> u64 {
+            self.next() % max
+        }
+    }
+
+    /// Levels of random sizes with random amounts of walls.
+    fn random_levels() -> impl Iterator<Item = (i32, i32, HashSet<GridCoords>)> {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        (0..RANDOM_LEVELS).map(move |_| {
+            let width = 1 + rng.below(16) as i32;
+            let height = 1 + rng.below(16) as i32;
+            let density = 1 + rng.below(100);
+
+            let walls = (0..height)
+                .flat_map(|y| (0..width).map(move |x| GridCoords { x, y }))
+                .filter(|_| rng.below(100) < density)
+                .collect();
+
+            (width, height, walls)
+        })
+    }
+
+    /// What merging every row into plates without stacking them gives.
+    fn row_merge_count(width: i32, height: i32, walls: &HashSet<GridCoords>) -> usize {
+        combine_walls_into_plates(width, height, walls)
+            .iter()
+            .map(Vec::len)
+            .sum()
+    }
+
+    #[test]
+    fn merged_rects_cover_exactly_the_walls() {
+        for (width, height, walls) in random_levels() {
+            let rects = merge_walls(width, height, &walls);
+
+            if let Err(error) = check_cover(&walls, &rects) {
+                panic!("{} in {}x{} level {:?}", error, width, height, walls);
+            }
+        }
+    }
+
+    #[test]
+    fn merging_never_adds_rects_over_plain_rows() {
+        for (width, height, walls) in random_levels() {
+            let rects = merge_walls(width, height, &walls);
+
+            assert!(rects.len() <= row_merge_count(width, height, &walls));
+        }
+    }
+
+    #[test]
+    fn stacked_plates_merge_into_one_rect() {
+        let walls = (1..4)
+            .flat_map(|y| (2..6).map(move |x| GridCoords { x, y }))
+            .collect();
+
+        assert_eq!(
+            merge_walls(8, 8, &walls),
+            vec![Rect {
+                left: 2,
+                right: 5,
+                top: 3,
+                bottom: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn check_cover_finds_overlaps_and_gaps() {
+        let walls: HashSet<GridCoords> = [GridCoords { x: 0, y: 0 }, GridCoords { x: 1, y: 0 }]
+            .into_iter()
+            .collect();
+        let tile = |x| Rect {
+            left: x,
+            right: x,
+            top: 0,
+            bottom: 0,
+        };
+
+        assert_eq!(
+            check_cover(&walls, &[tile(0), tile(0), tile(1)]),
+            Err(CoverError::Overlap(GridCoords { x: 0, y: 0 }))
+        );
+        assert_eq!(
+            check_cover(&walls, &[tile(0)]),
+            Err(CoverError::Uncovered(GridCoords { x: 1, y: 0 }))
+        );
+        assert_eq!(
+            check_cover(&walls, &[tile(2)]),
+            Err(CoverError::NotAWall(GridCoords { x: 2, y: 0 }))
+        );
+    }
+
+    #[test]
+    fn greedy_rects_cover_exactly_the_walls() {
+        for (width, height, walls) in random_levels() {
+            let rects = greedy_merge_walls(width, height, &walls);
+
+            if let Err(error) = check_cover(&walls, &rects) {
+                panic!("{} in {}x{} level {:?}", error, width, height, walls);
+            }
+        }
+    }
+
+    #[test]
+    fn greedy_takes_the_largest_rect_first() {
+        // An L: the 3x3 block beats the long but thin bottom row
+        let walls: HashSet<GridCoords> = (0..3)
+            .flat_map(|y| (0..3).map(move |x| GridCoords { x, y }))
+            .chain((3..8).map(|x| GridCoords { x, y: 0 }))
+            .collect();
+
+        let rects = greedy_merge_walls(8, 3, &walls);
+
+        assert_eq!(rects.len(), 2);
+        assert_eq!(rects[0].area(), 9);
+    }
+
+    fn tiles(coords: &[(i32, i32)]) -> HashSet<GridCoords> {
+        coords.iter().map(|&(x, y)| GridCoords { x, y }).collect()
+    }
+
+    /// Twice the area the outline encloses, positive when counter clockwise.
+    fn doubled_area(outline: &[IVec2]) -> i32 {
+        (0..outline.len())
+            .map(|i| {
+                let a = outline[i];
+                let b = outline[(i + 1) % outline.len()];
+                a.x * b.y - b.x * a.y
+            })
+            .sum()
+    }
+
+    /// Panics unless every loop is closed with straight sides and never
+    /// crosses or touches itself, and the loops enclose exactly the walls.
+    fn check_outlines(walls: &HashSet<GridCoords>, outlines: &[Vec<IVec2>]) {
+        for outline in outlines {
+            assert!(outline.len() >= 4, "{:?} is not a loop", outline);
+
+            let mut visited: HashSet<IVec2> = HashSet::new();
+            for i in 0..outline.len() {
+                let from = outline[i];
+                let to = outline[(i + 1) % outline.len()];
+                assert!(
+                    (from.x == to.x) != (from.y == to.y),
+                    "{:?} to {:?} is not a straight side of {:?}",
+                    from,
+                    to,
+                    outline
+                );
+
+                // Every corner a side passes, so crossings show up as repeats
+                let step = IVec2::new((to.x - from.x).signum(), (to.y - from.y).signum());
+                let mut corner = from;
+                while corner != to {
+                    assert!(
+                        visited.insert(corner),
+                        "{:?} intersects itself at {:?}",
+                        outline,
+                        corner
+                    );
+                    corner = corner + step;
+                }
+            }
+        }
+
+        // Holes go clockwise and take their area away again
+        let area: i32 = outlines.iter().map(|outline| doubled_area(outline)).sum();
+        assert_eq!(area, 2 * walls.len() as i32);
+    }
+
+    #[test]
+    fn single_tile_has_a_square_outline() {
+        let walls = tiles(&[(3, 5)]);
+        let outlines = trace_wall_outlines(&walls);
+
+        check_outlines(&walls, &outlines);
+        assert_eq!(
+            outlines,
+            vec![vec![
+                IVec2::new(3, 5),
+                IVec2::new(4, 5),
+                IVec2::new(4, 6),
+                IVec2::new(3, 6),
+            ]]
+        );
+    }
+
+    #[test]
+    fn holes_get_a_clockwise_outline_of_their_own() {
+        let walls: HashSet<GridCoords> = (0..3)
+            .flat_map(|y| (0..3).map(move |x| GridCoords { x, y }))
+            .filter(|&tile| tile != GridCoords { x: 1, y: 1 })
+            .collect();
+        let outlines = trace_wall_outlines(&walls);
+
+        check_outlines(&walls, &outlines);
+        assert_eq!(outlines.len(), 2);
+        assert_eq!(
+            outlines
+                .iter()
+                .filter(|outline| doubled_area(outline) < 0)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn diagonal_tiles_get_separate_outlines() {
+        let walls = tiles(&[(0, 0), (1, 1), (2, 0), (2, 2), (0, 2)]);
+        let outlines = trace_wall_outlines(&walls);
+
+        check_outlines(&walls, &outlines);
+        assert_eq!(outlines.len(), 5);
+    }
+
+    #[test]
+    fn diagonal_gaps_in_a_ring_keep_the_hole_separate() {
+        // A ring with its hole touching the outside at a single corner
+        let walls = tiles(&[(1, 0), (2, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        let outlines = trace_wall_outlines(&walls);
+
+        check_outlines(&walls, &outlines);
+        assert_eq!(outlines.len(), 2);
+    }
+
+    #[test]
+    fn random_outlines_are_closed_and_simple() {
+        for (_, _, walls) in random_levels() {
+            check_outlines(&walls, &trace_wall_outlines(&walls));
+        }
+    }
+}
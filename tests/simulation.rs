@@ -0,0 +1,528 @@
+//! Plays the game without a window and checks what happens in the world.
+
+use bevy::ecs::system::CommandQueue;
+use bevy::input::{keyboard::KeyboardInput, mouse::MouseButtonInput, ButtonState};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use iyes_loopless::prelude::*;
+use iyes_progress::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::FRAC_PI_2;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use graveyard_shift::{
+    components::wall_collisions::{spawn_wall_outline, spawn_wall_rect, Wall},
+    game_state::GameState,
+    headless_app,
+    mutant::{Hearing, HearingChecks, Mutant},
+    player::{Player, SoundPoint, SoundWave, SoundWaveEvent},
+    replay::{Recording, Replay, ReplayEvent},
+    timestep::fixed_timestep,
+    utils::wall_merge::{greedy_merge_walls, merge_walls, trace_wall_outlines, WallDecomposition},
+    HeadlessFrameTime,
+};
+#[cfg(feature = "dev-tools")]
+use graveyard_shift::{
+    dev_tools::{console::ConsoleCommand, DevToolsPlugin},
+    player::PendingPlayerPosition,
+};
+
+/// Identifier of the IntGrid layer the walls are painted on.
+const WALL_LAYER: &str = "Collider";
+/// Real time to wait for the assets to load before giving up.
+const LOADING_TIMEOUT: Duration = Duration::from_secs(30);
+/// Radius of a sound dot's collider, see `make_sound_dots`.
+const DOT_RADIUS: f32 = 1.;
+
+/// A headless app that has finished loading and is playing the first level.
+fn loaded_app() -> App {
+    wait_until_playing(headless_app())
+}
+
+/// Updates `app` until it has finished loading and is playing the first level.
+///
+/// Game time only moves by `HeadlessFrameTime` per update, however many
+/// updates loading takes. The timeout is in real time, since that's what
+/// the asset server's threads need to read the files.
+fn wait_until_playing(mut app: App) -> App {
+    let started = Instant::now();
+
+    loop {
+        app.update();
+        let state = app.world.resource::<CurrentState<GameState>>().0.clone();
+        if state == GameState::Playing {
+            return app;
+        }
+
+        if started.elapsed() > LOADING_TIMEOUT {
+            let Progress { done, total } = app
+                .world
+                .get_resource::<ProgressCounter>()
+                .map_or(Progress { done: 0, total: 0 }, |counter| counter.progress());
+            panic!(
+                "still in {:?} after {:?}, {} of {} loading steps done",
+                state, LOADING_TIMEOUT, done, total
+            );
+        }
+        thread::yield_now();
+    }
+}
+
+/// Updates until `done` is true, giving up after a while.
+fn update_until(app: &mut App, done: impl Fn(&App) -> bool) {
+    for _ in 0..1000 {
+        app.update();
+        if done(app) {
+            return;
+        }
+    }
+    panic!("gave up waiting");
+}
+
+fn send_key(app: &mut App, key: KeyCode, state: ButtonState) {
+    app.world.send_event(KeyboardInput {
+        scan_code: 0,
+        key_code: Some(key),
+        state,
+    });
+}
+
+fn press(app: &mut App, key: KeyCode) {
+    send_key(app, key, ButtonState::Pressed);
+}
+
+fn release(app: &mut App, key: KeyCode) {
+    send_key(app, key, ButtonState::Released);
+}
+
+/// Runs updates of `app` until `ticks` ticks have passed.
+///
+/// Frame times have to divide into ticks, or ticks into frame times,
+/// so ticks and updates line up again at the end.
+fn run_ticks(app: &mut App, ticks: u32) {
+    let frame_time = app.world.resource::<HeadlessFrameTime>().0;
+    let updates = (fixed_timestep() * ticks).as_nanos() / frame_time.as_nanos();
+    for _ in 0..updates {
+        app.update();
+    }
+}
+
+fn player_position(app: &mut App) -> Vec2 {
+    app.world
+        .query_filtered::<&GlobalTransform, With<Player>>()
+        .single(&app.world)
+        .translation()
+        .truncate()
+}
+
+fn sound_dots(app: &mut App) -> HashMap<Entity, (Vec2, Vec2)> {
+    app.world
+        .query_filtered::<(Entity, &GlobalTransform, &Velocity), With<SoundPoint>>()
+        .iter(&app.world)
+        .map(|(dot, transform, velocity)| {
+            (dot, (transform.translation().truncate(), velocity.linvel))
+        })
+        .collect()
+}
+
+/// The tiles of the spawned level that can be reached without crossing a wall.
+struct Floor {
+    origin: Vec2,
+    grid_size: f32,
+    tiles: HashSet<IVec2>,
+}
+
+impl Floor {
+    fn reachable_from(app: &mut App, start: Vec2) -> Self {
+        let (level_handle, level_transform) = app
+            .world
+            .query::<(&Handle<LdtkLevel>, &GlobalTransform)>()
+            .single(&app.world);
+        let origin = level_transform.translation().truncate();
+        let level = app
+            .world
+            .resource::<Assets<LdtkLevel>>()
+            .get(level_handle)
+            .expect("the spawned level is loaded");
+        let layer = level
+            .level
+            .layer_instances
+            .iter()
+            .flatten()
+            .find(|layer| layer.identifier == WALL_LAYER)
+            .expect("the level has a wall layer");
+        let (width, height, grid_size) = (layer.c_wid, layer.c_hei, layer.grid_size);
+
+        let walls: HashSet<IVec2> = app
+            .world
+            .query_filtered::<&GridCoords, With<Wall>>()
+            .iter(&app.world)
+            .map(|coords| IVec2::new(coords.x, coords.y))
+            .collect();
+
+        let mut floor = Self {
+            origin,
+            grid_size: grid_size as f32,
+            tiles: HashSet::new(),
+        };
+
+        let mut open = vec![floor.tile(start)];
+        while let Some(tile) = open.pop() {
+            let in_level = tile.x >= 0 && tile.y >= 0 && tile.x < width && tile.y < height;
+            if !in_level || walls.contains(&tile) || !floor.tiles.insert(tile) {
+                continue;
+            }
+            open.extend([IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|step| tile + step));
+        }
+
+        assert!(!floor.tiles.is_empty(), "the player spawned inside a wall");
+        floor
+    }
+
+    fn tile(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / self.grid_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    fn contains(&self, position: Vec2) -> bool {
+        self.tiles.contains(&self.tile(position))
+    }
+}
+
+#[test]
+fn player_cannot_pass_through_walls() {
+    let mut app = loaded_app();
+    let floor = Floor::reachable_from(&mut app, player_position(&mut app));
+
+    for key in [KeyCode::W, KeyCode::A, KeyCode::S, KeyCode::D] {
+        press(&mut app, key);
+        for _ in 0..240 {
+            app.update();
+            let position = player_position(&mut app);
+            assert!(
+                floor.contains(position),
+                "holding {:?} got the player through a wall to {}",
+                key,
+                position
+            );
+        }
+        release(&mut app, key);
+    }
+}
+
+#[test]
+fn sound_dots_bounce_off_walls() {
+    let mut app = loaded_app();
+    let floor = Floor::reachable_from(&mut app, player_position(&mut app));
+
+    press(&mut app, KeyCode::Space);
+    app.update();
+    release(&mut app, KeyCode::Space);
+
+    let start = sound_dots(&mut app);
+    assert_eq!(start.len(), usize::from(SoundWave::CLAP.number_of_dots));
+
+    // Stops just before the dots fade out
+    for _ in 0..110 {
+        app.update();
+        for (position, _) in sound_dots(&mut app).values() {
+            assert!(floor.contains(*position), "a dot escaped to {}", position);
+        }
+    }
+
+    let end = sound_dots(&mut app);
+    let bounced = end
+        .iter()
+        .filter(|(dot, (_, velocity))| start[dot].1.angle_between(*velocity).abs() > 0.01)
+        .count();
+    assert!(bounced > 0, "no dot hit a wall");
+
+    for (_, velocity) in end.values() {
+        assert!((velocity.length() - SoundWave::CLAP.speed).abs() < 0.01);
+    }
+}
+
+/// Fires a dot straight up along the left face of a wall built with
+/// `decomposition` and returns its velocity once it went past the middle.
+///
+/// The bottom half of the wall is wider than the top half,
+/// so rows and the greedy merge both split the cuboids there,
+/// leaving a seam halfway up the face the dot slides along.
+fn slide_along_wall(decomposition: WallDecomposition) -> Vec2 {
+    let mut app = loaded_app();
+
+    // Far away from the level, so only this wall is around
+    let scene_origin = Vec3::new(-10_000., -10_000., 0.);
+    let grid_size = 16;
+    let (width, height) = (21, 32);
+    let walls: HashSet<GridCoords> = (0..height)
+        .flat_map(|y| {
+            let right = if y < height / 2 { width - 1 } else { 5 };
+            (4..=right).map(move |x| GridCoords { x, y })
+        })
+        .collect();
+
+    let mut queue = CommandQueue::default();
+    Commands::new(&mut queue, &app.world)
+        .spawn_bundle(TransformBundle::from_transform(
+            Transform::from_translation(scene_origin),
+        ))
+        .with_children(|scene| match decomposition {
+            WallDecomposition::Rows => {
+                for rect in merge_walls(width, height, &walls) {
+                    spawn_wall_rect(scene, &rect, grid_size);
+                }
+            }
+            WallDecomposition::Greedy => {
+                for rect in greedy_merge_walls(width, height, &walls) {
+                    spawn_wall_rect(scene, &rect, grid_size);
+                }
+            }
+            WallDecomposition::Outline => {
+                for outline in trace_wall_outlines(&walls) {
+                    spawn_wall_outline(scene, &outline, grid_size);
+                }
+            }
+        });
+    queue.apply(&mut app.world);
+    app.update();
+
+    // Touching the left face of the wall, moving up along it
+    let origin = scene_origin
+        + Vec3::new(
+            4. * grid_size as f32 - DOT_RADIUS,
+            2. * grid_size as f32,
+            0.,
+        );
+    let wave = SoundWave {
+        number_of_dots: 1,
+        direction: FRAC_PI_2,
+        spread: 0.,
+        speed: 100.,
+        lifetime: 5.,
+        cost: 0.,
+    };
+    app.world.send_event(SoundWaveEvent { origin, wave });
+
+    for _ in 0..240 {
+        app.update();
+    }
+
+    let (position, velocity) = sound_dots(&mut app)
+        .into_values()
+        .find(|(position, _)| position.distance(origin.truncate()) < 1000.)
+        .expect("the dot is still alive");
+    assert!(
+        position.y > scene_origin.y + (height / 2 * grid_size) as f32,
+        "the dot never got past the middle of the wall: {}",
+        position
+    );
+
+    velocity
+}
+
+/// Seams between wall colliders used to bounce dots that slide along a wall.
+#[test]
+fn sound_dots_slide_along_long_walls() {
+    let sliding = |velocity: Vec2| velocity.distance(Vec2::new(0., 100.)) < 0.01;
+
+    let outline = slide_along_wall(WallDecomposition::Outline);
+    assert!(
+        sliding(outline),
+        "the dot bounced off the outline it slides along: {}",
+        outline
+    );
+
+    let cuboids = [WallDecomposition::Rows, WallDecomposition::Greedy]
+        .map(|decomposition| (decomposition, slide_along_wall(decomposition)));
+    assert!(
+        cuboids.iter().any(|&(_, velocity)| !sliding(velocity)),
+        "no seam between cuboids deflected the dot, the scene doesn't test anything: {:?}",
+        cuboids
+    );
+}
+
+#[test]
+fn mutants_hear_sounds_through_the_spatial_index() {
+    let mut app = loaded_app();
+    let (mutant, origin) = app
+        .world
+        .query_filtered::<(Entity, &GlobalTransform), With<Mutant>>()
+        .iter(&app.world)
+        .map(|(mutant, transform)| (mutant, transform.translation()))
+        .next()
+        .expect("the level has mutants");
+
+    app.world.send_event(SoundWaveEvent {
+        origin,
+        wave: SoundWave::CLAP,
+    });
+    run_ticks(&mut app, 10);
+
+    assert!(
+        app.world.get::<Hearing>(mutant).unwrap().heard.is_some(),
+        "the mutant didn't hear a clap right next to it"
+    );
+
+    // Only the mutant next to the clap has dots in its cells
+    let checks = *app.world.resource::<HearingChecks>();
+    assert!(
+        checks.candidates * 4 < checks.without_index,
+        "the index didn't save any checks: {:?}",
+        checks
+    );
+}
+
+/// Gamepads and headless apps have no cursor to aim at.
+#[test]
+fn aimed_sounds_without_a_cursor_go_the_way_the_player_looks() {
+    let mut app = loaded_app();
+
+    // The player's art faces left, so turn right first
+    press(&mut app, KeyCode::D);
+    run_ticks(&mut app, 10);
+    release(&mut app, KeyCode::D);
+    run_ticks(&mut app, 30);
+
+    let button = MouseButton::Left;
+    app.world.send_event(MouseButtonInput {
+        button,
+        state: ButtonState::Pressed,
+    });
+    app.update();
+    app.world.send_event(MouseButtonInput {
+        button,
+        state: ButtonState::Released,
+    });
+    app.update();
+
+    let dots = sound_dots(&mut app);
+    assert_eq!(
+        dots.len(),
+        usize::from(SoundWave::aimed(Vec2::X).number_of_dots)
+    );
+    for (_, velocity) in dots.values() {
+        assert!(velocity.x > 0., "a dot went the wrong way: {}", velocity);
+    }
+}
+
+/// Walks right and stops again, with every update taking `frame_time`.
+fn walk_right(frame_time: Duration) -> Vec2 {
+    let mut app = loaded_app();
+    app.insert_resource(HeadlessFrameTime(frame_time));
+    let start = player_position(&mut app);
+
+    press(&mut app, KeyCode::D);
+    run_ticks(&mut app, 60);
+    release(&mut app, KeyCode::D);
+    // Lets the player come to a stop
+    run_ticks(&mut app, 30);
+
+    player_position(&mut app) - start
+}
+
+/// Drawing the player between ticks used to drag its rigid body back.
+#[test]
+fn movement_is_the_same_at_any_frame_rate() {
+    let at_30_fps = walk_right(fixed_timestep() * 2);
+    let at_120_fps = walk_right(fixed_timestep() / 2);
+
+    assert!(at_30_fps.x > 10., "the player didn't move: {}", at_30_fps);
+    assert!(
+        at_30_fps.distance(at_120_fps) < 0.01,
+        "walked {} at 30 fps but {} at 120 fps",
+        at_30_fps,
+        at_120_fps
+    );
+}
+
+/// Plays `recording` back with every update taking `frame_time`.
+fn replay(recording: &Recording, frame_time: Duration) -> Vec2 {
+    let mut app = loaded_app();
+    app.insert_resource(HeadlessFrameTime(frame_time));
+    app.world
+        .send_event(ReplayEvent::StartReplay(recording.clone()));
+    update_until(&mut app, |app| {
+        matches!(app.world.resource::<Replay>(), Replay::Idle)
+    });
+
+    player_position(&mut app)
+}
+
+#[test]
+fn replays_end_where_the_recording_did() {
+    let mut app = loaded_app();
+    app.world.send_event(ReplayEvent::StartRecording);
+    update_until(
+        &mut app,
+        |app| matches!(app.world.resource::<Replay>(), Replay::Recording(session) if session.started),
+    );
+
+    press(&mut app, KeyCode::D);
+    run_ticks(&mut app, 60);
+    release(&mut app, KeyCode::D);
+    press(&mut app, KeyCode::Space);
+    app.update();
+    release(&mut app, KeyCode::Space);
+    // Lets the player come to a stop
+    run_ticks(&mut app, 30);
+
+    let recorded_position = player_position(&mut app);
+    let recording = match app.world.resource::<Replay>() {
+        Replay::Recording(session) => session.recording.clone(),
+        _ => panic!("stopped recording"),
+    };
+
+    // Replays are played back tick by tick, so the frame rate doesn't matter
+    for frame_time in [fixed_timestep(), fixed_timestep() * 2, fixed_timestep() / 2] {
+        let replayed_position = replay(&recording, frame_time);
+        assert!(
+            replayed_position.distance(recorded_position) < 0.1,
+            "recorded {} but replayed {} with {:?} frames",
+            recorded_position,
+            replayed_position,
+            frame_time
+        );
+    }
+}
+
+#[cfg(feature = "dev-tools")]
+#[test]
+fn console_commands_run_headless() {
+    let mut app = headless_app();
+    app.add_plugin(DevToolsPlugin);
+    let mut app = wait_until_playing(app);
+
+    app.world.send_event(ConsoleCommand::Stress(3));
+    run_ticks(&mut app, 2);
+
+    assert_eq!(
+        sound_dots(&mut app).len(),
+        3 * usize::from(SoundWave::CLAP.number_of_dots)
+    );
+}
+
+#[cfg(feature = "dev-tools")]
+#[test]
+fn reveal_all_respawns_the_world_around_the_player() {
+    let mut app = headless_app();
+    app.add_plugin(DevToolsPlugin);
+    let mut app = wait_until_playing(app);
+    let start = player_position(&mut app);
+
+    app.world.send_event(ConsoleCommand::RevealAll);
+    app.update();
+    assert!(matches!(
+        app.world.resource::<LdtkSettings>().int_grid_rendering,
+        IntGridRendering::Colorful
+    ));
+
+    update_until(&mut app, |app| {
+        !app.world.contains_resource::<PendingPlayerPosition>()
+    });
+    assert!(player_position(&mut app).distance(start) < 1.);
+}